@@ -0,0 +1,425 @@
+//! Fast-path executor for boards of up to 8x8 cells, where the occupancy of the whole board fits in
+//! a [`u64`] and moves and views become shifts and masks.
+//!
+//! [`BitboardExecutor`] produces the same results as [`crate::executor::PGExecutor`], but passes
+//! a [`Bitboard`] rather than a [`Board`](parking_game::Board) to its observers, which must
+//! implement [`BitboardObserver`].
+
+use crate::input::PGInput;
+use crate::observers::{IllegalMoveKind, View, ViewFrom};
+use libafl::executors::{Executor, ExitKind, HasObservers};
+use libafl::state::HasExecutions;
+use libafl_bolts::Error;
+use libafl_bolts::tuples::RefIndexable;
+use parking_game::{BoardValue, Car, Direction, Orientation, State};
+use std::num::NonZeroUsize;
+
+/// The number of bits per row (and per column, in the transposed occupancy).
+const STRIDE: usize = 8;
+
+/// A board of at most 8x8 cells, with the occupancy stored as bits: the cell at `(row, column)` is
+/// bit `row * 8 + column` of the occupancy, and bit `column * 8 + row` of the transposed occupancy,
+/// so that both rows and columns may be scanned with shifts.
+#[derive(Debug, Clone)]
+pub struct Bitboard<T> {
+    rows: usize,
+    columns: usize,
+    occupancy: u64,
+    transposed: u64,
+    /// The car occupying each cell (by bit of the occupancy), or zero for none.
+    cells: [u8; STRIDE * STRIDE],
+    positions: Vec<(usize, usize)>,
+    cars: Vec<Car<T>>,
+}
+
+impl<T> Bitboard<T>
+where
+    T: BoardValue,
+{
+    /// Creates a bitboard with the same cars as the provided state. Fails if the board is larger
+    /// than 8x8 or has more than 255 cars.
+    pub fn new(state: &State<T>) -> Result<Self, Error> {
+        let dim = state.dimensions();
+        let (rows, columns) = ((*dim.rows()).into(), (*dim.columns()).into());
+        if rows > STRIDE || columns > STRIDE {
+            return Err(Error::illegal_argument(format!(
+                "bitboards support at most {STRIDE}x{STRIDE} cells, but the board is {rows}x{columns}"
+            )));
+        }
+        if state.cars().len() > u8::MAX as usize {
+            return Err(Error::illegal_argument(
+                "bitboards support at most 255 cars".to_string(),
+            ));
+        }
+
+        let mut board = Self {
+            rows,
+            columns,
+            occupancy: 0,
+            transposed: 0,
+            cells: [0; STRIDE * STRIDE],
+            positions: Vec::with_capacity(state.cars().len()),
+            cars: Vec::with_capacity(state.cars().len()),
+        };
+        for (i, &(position, car)) in state.cars().iter().enumerate() {
+            let position = ((*position.row()).into(), (*position.column()).into());
+            board.positions.push(position);
+            board.cars.push(car);
+            for (row, column) in board.cells_of(i) {
+                board.set(row, column, i as u8 + 1);
+            }
+        }
+        Ok(board)
+    }
+
+    /// Converts this bitboard back into a state.
+    pub fn to_state(&self) -> State<T> {
+        let value = |v: usize| T::try_from(v).unwrap_or_else(|_| unreachable!());
+        let mut state = State::empty((value(self.rows), value(self.columns)))
+            .unwrap_or_else(|_| unreachable!());
+        let mut board = state.board_mut().unwrap();
+        for (&(row, column), &car) in self.positions.iter().zip(&self.cars) {
+            board
+                .add_car((value(row), value(column)), car)
+                .unwrap_or_else(|_| unreachable!());
+        }
+        drop(board);
+        state
+    }
+
+    /// The number of cars on the board.
+    pub fn cars(&self) -> usize {
+        self.cars.len()
+    }
+
+    /// Shifts the provided car one cell in the provided direction, like
+    /// [`parking_game::Board::shift_car`].
+    pub fn shift_car(
+        &mut self,
+        car: NonZeroUsize,
+        direction: Direction,
+    ) -> Result<(), IllegalMoveKind> {
+        let idx = car.get() - 1;
+        let Some(&(row, column)) = self.positions.get(idx) else {
+            return Err(IllegalMoveKind::InvalidCar);
+        };
+        let length = (*self.cars[idx].length()).into();
+        // the cell the car enters and the cell it leaves
+        let (entered, left, moved) = match (direction, self.cars[idx].orientation()) {
+            (Direction::Up, Orientation::UpDown) => (
+                row.checked_sub(1).map(|row| (row, column)),
+                (row + length - 1, column),
+                row.checked_sub(1).map(|row| (row, column)),
+            ),
+            (Direction::Down, Orientation::UpDown) => (
+                Some((row + length, column)).filter(|&(row, _)| row < self.rows),
+                (row, column),
+                Some((row + 1, column)),
+            ),
+            (Direction::Left, Orientation::LeftRight) => (
+                column.checked_sub(1).map(|column| (row, column)),
+                (row, column + length - 1),
+                column.checked_sub(1).map(|column| (row, column)),
+            ),
+            (Direction::Right, Orientation::LeftRight) => (
+                Some((row, column + length)).filter(|&(_, column)| column < self.columns),
+                (row, column),
+                Some((row, column + 1)),
+            ),
+            _ => return Err(IllegalMoveKind::InvalidDirection),
+        };
+        let (Some(entered), Some(moved)) = (entered, moved) else {
+            return Err(IllegalMoveKind::InvalidFinalPosition);
+        };
+        if let Some(other) = self.get(entered.0, entered.1) {
+            return Err(IllegalMoveKind::Intersects(other));
+        }
+
+        self.set(left.0, left.1, 0);
+        self.set(entered.0, entered.1, car.get() as u8);
+        self.positions[idx] = moved;
+        Ok(())
+    }
+
+    /// Computes the [`ViewFrom`] for each car on the board, in car order, like
+    /// [`crate::observers::ViewObserver`].
+    pub fn views(&self) -> impl Iterator<Item = ViewFrom<T>> + '_ {
+        let value = |v: usize| T::try_from(v).unwrap_or_else(|_| unreachable!());
+        self.positions
+            .iter()
+            .zip(&self.cars)
+            .map(move |(&(row, column), car)| {
+                let length = (*car.length()).into();
+                // scan along the lane: the row in the occupancy, or the column in the transposed
+                let (bits, lane, start, end, backward, forward) = match car.orientation() {
+                    Orientation::UpDown => (
+                        self.transposed,
+                        column,
+                        row,
+                        self.rows,
+                        Direction::Up,
+                        Direction::Down,
+                    ),
+                    Orientation::LeftRight => (
+                        self.occupancy,
+                        row,
+                        column,
+                        self.columns,
+                        Direction::Left,
+                        Direction::Right,
+                    ),
+                };
+                let lane_bits = (bits >> (lane * STRIDE)) & 0xff;
+                let cell = |offset: usize| match car.orientation() {
+                    Orientation::UpDown => self.get(offset, column),
+                    Orientation::LeftRight => self.get(row, offset),
+                };
+
+                let behind = lane_bits & ((1 << start) - 1);
+                let backward = if behind == 0 {
+                    View::new(backward, None, value(start))
+                } else {
+                    let nearest = (u64::BITS - 1 - behind.leading_zeros()) as usize;
+                    View::new(backward, cell(nearest), value(start - nearest - 1))
+                };
+
+                let ahead = lane_bits >> (start + length);
+                let forward = if ahead == 0 {
+                    View::new(forward, None, value(end - start - length))
+                } else {
+                    let nearest = ahead.trailing_zeros() as usize;
+                    View::new(forward, cell(start + length + nearest), value(nearest))
+                };
+
+                ViewFrom::new(backward, forward)
+            })
+    }
+
+    fn cells_of(&self, idx: usize) -> impl Iterator<Item = (usize, usize)> + use<T> {
+        let (row, column) = self.positions[idx];
+        let orientation = self.cars[idx].orientation();
+        (0..(*self.cars[idx].length()).into()).map(move |offset| match orientation {
+            Orientation::UpDown => (row + offset, column),
+            Orientation::LeftRight => (row, column + offset),
+        })
+    }
+
+    fn get(&self, row: usize, column: usize) -> Option<NonZeroUsize> {
+        NonZeroUsize::new(self.cells[row * STRIDE + column] as usize)
+    }
+
+    fn set(&mut self, row: usize, column: usize, car: u8) {
+        let (bit, transposed) = (1 << (row * STRIDE + column), 1 << (column * STRIDE + row));
+        if car == 0 {
+            self.occupancy &= !bit;
+            self.transposed &= !transposed;
+        } else {
+            self.occupancy |= bit;
+            self.transposed |= transposed;
+        }
+        self.cells[row * STRIDE + column] = car;
+    }
+}
+
+/// An [`libafl::observers::Observer`] compatible with [`BitboardExecutor`].
+pub trait BitboardObserver<T> {
+    /// Passes the final board to the observer, called after [`libafl::observers::Observer::pre_exec`]
+    /// and before [`libafl::observers::Observer::post_exec`] if the execution completes normally.
+    /// Does nothing by default.
+    #[allow(unused_variables)]
+    fn final_bitboard(&mut self, board: &Bitboard<T>) {
+        // do nothing
+    }
+}
+
+/// Utility trait for marking [`libafl_bolts::tuples::tuple_list`]s as "all bitboard observers";
+/// see [`crate::observers::PGObserverTuple`].
+pub trait BitboardObserverTuple<T> {
+    /// Iterate all observers contained here and pass the provided board.
+    fn final_bitboard_all(&mut self, board: &Bitboard<T>);
+}
+
+impl<T> BitboardObserverTuple<T> for () {
+    fn final_bitboard_all(&mut self, _board: &Bitboard<T>) {
+        // this is the end of the list, so we're done
+    }
+}
+
+impl<T, Head, Tail> BitboardObserverTuple<T> for (Head, Tail)
+where
+    Head: BitboardObserver<T>,
+    Tail: BitboardObserverTuple<T>,
+{
+    fn final_bitboard_all(&mut self, board: &Bitboard<T>) {
+        self.0.final_bitboard(board);
+        self.1.final_bitboard_all(board);
+    }
+}
+
+/// Executor which advances a [`Bitboard`] by "running" the move sequence provided. Unlike
+/// [`crate::executor::PGExecutor`], this always replays the whole input from the initial board,
+/// which is cheap enough that snapshots are not worth loading.
+///
+/// Only the final board is passed to the observers: there is no [`Board`](parking_game::Board) to
+/// pass to the per-move hooks of [`crate::observers::PGObserver`], so observers which rely on them
+/// (e.g., [`crate::observers::TraceObserver`] or [`crate::observers::IllegalMoveObserver`]) can't
+/// be used with this executor.
+pub struct BitboardExecutor<T, OT> {
+    initial: Bitboard<T>,
+    observers: OT,
+}
+
+impl<T, OT> BitboardExecutor<T, OT>
+where
+    T: BoardValue,
+{
+    /// Create a new executor for the provided state with the provided observers. Fails if the
+    /// state is not supported by [`Bitboard`].
+    pub fn new(initial: &State<T>, observers: OT) -> Result<Self, Error> {
+        Ok(Self {
+            initial: Bitboard::new(initial)?,
+            observers,
+        })
+    }
+}
+
+impl<T, OT> BitboardExecutor<T, OT> {
+    /// The initial board which this executor is going to advance.
+    pub fn initial(&self) -> &Bitboard<T> {
+        &self.initial
+    }
+}
+
+impl<T, OT> HasObservers for BitboardExecutor<T, OT> {
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&Self::Observers, Self::Observers> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut Self::Observers, Self::Observers> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+impl<EM, OT, S, T, Z> Executor<EM, PGInput, S, Z> for BitboardExecutor<T, OT>
+where
+    OT: BitboardObserverTuple<T>,
+    S: HasExecutions,
+    T: BoardValue,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &PGInput,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+
+        let mut board = self.initial.clone();
+        for &(car, direction) in input.moves() {
+            if board.shift_car(car, direction).is_err() {
+                return Ok(ExitKind::Crash);
+            }
+        }
+
+        self.observers.final_bitboard_all(&board);
+
+        Ok(ExitKind::Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::bitboard::BitboardExecutor;
+    use crate::executor::PGExecutor;
+    use crate::input::PGInput;
+    use crate::observers::{FinalStateObserver, StateEncoding, ViewObserver};
+    use libafl::NopFuzzer;
+    use libafl::events::SimpleEventManager;
+    use libafl::executors::{Executor, HasObservers};
+    use libafl::observers::ObserversTuple;
+    use libafl::state::NopState;
+    use libafl_bolts::rands::{Rand, StdRand};
+    use libafl_bolts::tuples::tuple_list;
+    use parking_game::Direction;
+    use std::error::Error;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn differential() -> Result<(), Box<dyn Error>> {
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+        let mut rand = StdRand::with_seed(0);
+
+        for map in [
+            include_str!("../maps/tokyo1.map"),
+            include_str!("../maps/tokyo36.map"),
+            "oo.\n.22",
+        ] {
+            let initial = crate::parse_map::<u8>(map);
+            let cars = initial.cars().len();
+            let mut reference = PGExecutor::new(
+                initial.clone(),
+                tuple_list!(
+                    ViewObserver::<u8>::default(),
+                    FinalStateObserver::<u8>::default()
+                ),
+            );
+            let mut bitboard = BitboardExecutor::new(
+                &initial,
+                tuple_list!(
+                    ViewObserver::<u8>::default(),
+                    FinalStateObserver::<u8>::default()
+                ),
+            )?;
+
+            for _ in 0..1000 {
+                // mostly valid moves, but with some invalid cars and directions thrown in
+                let moves = (0..rand.between(0, 20))
+                    .map(|_| {
+                        let car = NonZeroUsize::new(rand.between(1, cars + 1)).unwrap();
+                        let direction = *rand
+                            .choose(&[
+                                Direction::Up,
+                                Direction::Down,
+                                Direction::Left,
+                                Direction::Right,
+                            ])
+                            .unwrap();
+                        (car, direction)
+                    })
+                    .collect();
+                let input = PGInput::new(moves);
+
+                reference.observers_mut().pre_exec_all(&mut state, &input)?;
+                let expected = reference.run_target(&mut fuzzer, &mut state, &mut mgr, &input)?;
+                reference
+                    .observers_mut()
+                    .post_exec_all(&mut state, &input, &expected)?;
+
+                bitboard.observers_mut().pre_exec_all(&mut state, &input)?;
+                let actual = bitboard.run_target(&mut fuzzer, &mut state, &mut mgr, &input)?;
+                bitboard
+                    .observers_mut()
+                    .post_exec_all(&mut state, &input, &actual)?;
+
+                assert_eq!(expected, actual);
+                let (expected, actual) = (&reference.observers().0, &bitboard.observers().0);
+                assert_eq!(expected.views().count(), actual.views().count());
+                for ((_, expected), (_, actual)) in expected.views().zip(actual.views()) {
+                    assert_eq!(expected.backward(), actual.backward());
+                    assert_eq!(expected.forward(), actual.forward());
+                }
+                assert_eq!(
+                    reference.observers().1.0.encoding(),
+                    bitboard.observers().1.0.encoding()
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::input::PGInput;
 use crate::observers::PGObserverTuple;
+use crate::snapshots::{PrefixHashes, SnapshotCache};
 use libafl::executors::{Executor, ExitKind, HasObservers};
 use libafl::state::{HasCurrentTestcase, HasExecutions};
 use libafl_bolts::Error;
@@ -12,12 +13,36 @@ use parking_game::{BoardValue, State};
 pub struct PGExecutor<T, OT> {
     initial: State<T>,
     observers: OT,
+    snapshots: Option<SnapshotCache<T>>,
 }
 
 impl<T, OT> PGExecutor<T, OT> {
     /// Create a new executor for the provided state with the provided observers.
     pub fn new(initial: State<T>, observers: OT) -> Self {
-        Self { initial, observers }
+        Self {
+            initial,
+            observers,
+            snapshots: None,
+        }
+    }
+
+    /// Create a new executor for the provided state with the provided observers, which caches the
+    /// final state of each execution in the provided [`SnapshotCache`] and resumes each execution
+    /// from the nearest cached state.
+    ///
+    /// An execution which resumes passes the cached state to the observers with
+    /// [`PGObserver::initial_board`](crate::observers::PGObserver::initial_board), along with the
+    /// number of moves it already reflects, and only the moves after those to
+    /// [`PGObserver::moved`](crate::observers::PGObserver::moved). Whether an execution resumes
+    /// depends on what is cached, i.e. on the executions before it, so observers which need every
+    /// move of the input (e.g., [`crate::observers::TraceObserver`] for cycle detection) may see
+    /// different moves for the same input. Use [`PGExecutor::new`] with those observers.
+    pub fn with_snapshots(initial: State<T>, observers: OT, snapshots: SnapshotCache<T>) -> Self {
+        Self {
+            initial,
+            observers,
+            snapshots: Some(snapshots),
+        }
     }
 }
 
@@ -26,6 +51,11 @@ impl<T, OT> PGExecutor<T, OT> {
     pub fn initial(&self) -> &State<T> {
         &self.initial
     }
+
+    /// The snapshot cache of this executor, if it has one.
+    pub fn snapshots(&self) -> Option<&SnapshotCache<T>> {
+        self.snapshots.as_ref()
+    }
 }
 
 // This allows other components to interact with the executors observers, when necessary.
@@ -57,6 +87,9 @@ where
         // first: increment the executions for tracking how many times we've run so far
         *state.executions_mut() += 1;
 
+        // hashed once, both to look up the nearest cached state and to cache the final one
+        let prefixes = self.snapshots.as_ref().map(|_| PrefixHashes::new(input));
+
         let (mut state, moves) = (|| {
             // this is a closure which allows us to do better control flow
             // you can `return` values in this block to assign them to the variables above
@@ -71,6 +104,16 @@ where
             //    - the prefix of moves are the same
             //    - the returned sequence of moves is after that prefix (use the slice operator)
 
+            // resume from the state after the longest prefix of moves we have cached, if any
+            if let Some((applied, state)) = self
+                .snapshots
+                .as_mut()
+                .zip(prefixes.as_ref())
+                .and_then(|(snapshots, prefixes)| snapshots.nearest(prefixes))
+            {
+                return Ok((state, &input.moves()[applied..]));
+            }
+
             // create a local copy of the initial instance and get the moves we're about to apply
             Ok::<_, Error>((self.initial.clone(), input.moves()))
         })()?;
@@ -79,13 +122,30 @@ where
             .board_mut()
             .map_err(|e| Error::illegal_state(e.to_string()))?;
 
+        // send the board we start from to all the observers, and how many moves it already reflects
+        let applied = input.moves().len() - moves.len();
+        self.observers.initial_board_all(applied, &board);
+
         // TODO(pt.0): apply the moves in sequence
         //  - check the docs for how to apply moves to a board
         //    - see: https://docs.rs/parking-game/latest/parking_game/struct.Board.html
         //  - if an error occurs during a move, return `Ok(ExitKind::Crash)`.
+        //    - before returning, send the move to all the observers with
+        //      `self.observers.illegal_move_all(index, car, direction, &board)`, where `board` is
+        //      the board the move was attempted on; don't call `final_board_all` afterwards
+        //  - after each move, send it to all the observers with
+        //    `self.observers.moved_all(index, car, direction, &board)`, where `board` is the board
+        //    after the move
+        //    - `index` is the index of the move in the whole input, so offset the index in `moves`
+        //      by the moves that were `applied` already (e.g., `applied + i`)
         // TODO(pt.3): add a microsecond delay *after each move* to simulate cost:
         // sleep(Duration::from_micros(1));
 
+        // cache where we ended up, so inputs mutated from this one can resume from here
+        if let (Some(snapshots), Some(prefixes)) = (&mut self.snapshots, &prefixes) {
+            snapshots.insert(prefixes, board.state());
+        }
+
         // send the final board to all the observers
         self.observers.final_board_all(&board);
 
@@ -98,7 +158,8 @@ where
 mod test {
     use crate::executor::PGExecutor;
     use crate::input::PGInput;
-    use crate::observers::FinalStateObserver;
+    use crate::observers::{FinalStateObserver, IllegalMoveObserver, TraceObserver};
+    use crate::snapshots::SnapshotCache;
     use libafl::NopFuzzer;
     use libafl::events::SimpleEventManager;
     use libafl::executors::{Executor, ExitKind, HasObservers};
@@ -151,4 +212,117 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn cached_run_check() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo..
+        .22.
+        "#,
+        );
+        let mut plain = PGExecutor::new(
+            initial.clone(),
+            tuple_list!(FinalStateObserver::<u8>::default()),
+        );
+        let mut cached = PGExecutor::with_snapshots(
+            initial,
+            tuple_list!(FinalStateObserver::<u8>::default()),
+            SnapshotCache::new(1 << 20),
+        );
+
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let inputs = [
+            vec![(one, Direction::Right)],
+            vec![(one, Direction::Right), (two, Direction::Right)],
+            vec![(one, Direction::Right), (two, Direction::Left)],
+            vec![(one, Direction::Right), (one, Direction::Right)],
+            vec![(one, Direction::Right), (one, Direction::Down)],
+            vec![(one, Direction::Right), (two, Direction::Right)],
+        ];
+        for input in inputs.map(PGInput::new) {
+            let mut run = |executor: &mut PGExecutor<u8, (FinalStateObserver<u8>, ())>| -> Result<_, Box<dyn Error>> {
+                executor.observers_mut().pre_exec_all(&mut state, &input)?;
+                let kind = executor.run_target(&mut fuzzer, &mut state, &mut mgr, &input)?;
+                executor
+                    .observers_mut()
+                    .post_exec_all(&mut state, &input, &kind)?;
+                let columns = executor.observers.0.final_state().map(|state| {
+                    state
+                        .cars()
+                        .iter()
+                        .map(|(position, _)| *position.column())
+                        .collect::<Vec<_>>()
+                });
+                Ok((kind, columns))
+            };
+            assert_eq!(run(&mut plain)?, run(&mut cached)?);
+        }
+
+        // every input after the first resumed from it, and the last one from itself
+        let snapshots = cached.snapshots().unwrap();
+        assert_eq!((5, 1), (snapshots.hits(), snapshots.misses()));
+        assert_eq!(4, snapshots.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn per_move_hooks() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo..
+        .22.
+        "#,
+        );
+        let mut executor = PGExecutor::with_snapshots(
+            initial,
+            tuple_list!(
+                TraceObserver::<u8>::new(false),
+                IllegalMoveObserver::default()
+            ),
+            SnapshotCache::new(1 << 20),
+        );
+
+        let mut fuzzer = NopFuzzer::new();
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let first = PGInput::new(vec![(one, Direction::Right), (two, Direction::Left)]);
+        executor.observers_mut().pre_exec_all(&mut state, &first)?;
+        let kind = executor.run_target(&mut fuzzer, &mut state, &mut mgr, &first)?;
+        executor
+            .observers_mut()
+            .post_exec_all(&mut state, &first, &kind)?;
+
+        assert_eq!(ExitKind::Ok, kind);
+        // the starting board and one per move
+        assert_eq!(3, executor.observers.0.hashes().len());
+        assert!(executor.observers.1.0.illegal().is_none());
+
+        // resumes after the first two moves, so the third is the first the observers see
+        let second = PGInput::new(vec![
+            (one, Direction::Right),
+            (two, Direction::Left),
+            (one, Direction::Right),
+            (one, Direction::Right),
+        ]);
+        executor.observers_mut().pre_exec_all(&mut state, &second)?;
+        let kind = executor.run_target(&mut fuzzer, &mut state, &mut mgr, &second)?;
+        executor
+            .observers_mut()
+            .post_exec_all(&mut state, &second, &kind)?;
+
+        assert_eq!(ExitKind::Crash, kind);
+        assert_eq!(2, executor.observers.0.applied());
+        assert_eq!(2, executor.observers.0.hashes().len());
+        assert_eq!(3, executor.observers.1.0.illegal().unwrap().index());
+
+        Ok(())
+    }
 }
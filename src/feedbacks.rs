@@ -2,19 +2,27 @@
 //! by the [`libafl::observers::Observer`] implementations in [`crate::observers`].
 
 use crate::input::PGInput;
-use crate::observers::{FinalStateObserver, ViewFrom, ViewObserver};
+use crate::mutators::{MacroDictionaryMetadata, MutatorStatsMetadata};
+use crate::observers::{
+    BlockerObserver, FinalStateObserver, IllegalMove, IllegalMoveKind, IllegalMoveObserver,
+    MobilityObserver, StateEncoding, ViewFrom, ViewObserver,
+};
+use crate::schedulers::RetirementMetadata;
 use libafl::HasMetadata;
-use libafl::corpus::Testcase;
+use libafl::corpus::{Corpus, Testcase};
 use libafl::events::{Event, EventFirer, EventWithStats, ExecStats};
 use libafl::executors::ExitKind;
 use libafl::feedbacks::{Feedback, StateInitializer};
-use libafl::monitors::stats::{AggregatorOps, UserStats};
+use libafl::monitors::stats::{AggregatorOps, UserStats, UserStatsValue};
+use libafl::observers::ObserverWithHashField;
+use libafl::state::{HasCorpus, HasExecutions};
 use libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
 use libafl_bolts::{Error, Named, current_time, impl_serdeany};
-use parking_game::{BoardValue, State};
+use parking_game::{BoardValue, Direction, State};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::num::NonZeroUsize;
 
@@ -46,6 +54,11 @@ pub struct ViewMetadata<T> {
 }
 
 impl<T> ViewMetadata<T> {
+    #[cfg(test)]
+    pub(crate) fn new(views: Vec<ViewFrom<T>>) -> Self {
+        Self { views }
+    }
+
     /// An iterator over what's viewable by each car. The objective car will be the first entry.
     pub fn views(&self) -> impl Iterator<Item = (NonZeroUsize, &ViewFrom<T>)> {
         self.views
@@ -119,6 +132,11 @@ pub struct FinalStateMetadata<T> {
 }
 
 impl<T> FinalStateMetadata<T> {
+    #[cfg(test)]
+    pub(crate) fn new(state: State<T>) -> Self {
+        Self { state }
+    }
+
     /// The state contained within this metadata.
     pub fn state(&self) -> &State<T> {
         &self.state
@@ -154,6 +172,149 @@ where
     }
 }
 
+/// Feedback which replays each new testcase from the initial state and stashes a snapshot of the
+/// board every few moves. Unlike [`ViewFeedback`] and [`FinalStateFeedback`], this lets mutators
+/// make valid edits anywhere in the input, not just at the end.
+pub struct PrefixSnapshotFeedback<T> {
+    initial: State<T>,
+    interval: NonZeroUsize,
+}
+
+/// The default number of moves between two checkpoints of a [`PrefixSnapshotFeedback`].
+const CHECKPOINT_INTERVAL: usize = 16;
+
+impl<T> PrefixSnapshotFeedback<T>
+where
+    T: Clone,
+{
+    /// Create a new [`PrefixSnapshotFeedback`] which replays testcases from the provided state.
+    pub fn new(initial: &State<T>) -> Self {
+        Self::with_interval(initial, NonZeroUsize::new(CHECKPOINT_INTERVAL).unwrap())
+    }
+
+    /// Create a new [`PrefixSnapshotFeedback`] which keeps a checkpoint every `interval` moves.
+    /// Smaller intervals make lookups cheaper at the cost of more memory per testcase.
+    pub fn with_interval(initial: &State<T>, interval: NonZeroUsize) -> Self {
+        Self {
+            initial: initial.clone(),
+            interval,
+        }
+    }
+}
+
+impl<S, T> StateInitializer<S> for PrefixSnapshotFeedback<T> {}
+
+impl<T> Named for PrefixSnapshotFeedback<T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_prefix_fb");
+        &NAME
+    }
+}
+
+/// Metadata which holds sparse checkpoints of the board while replaying the moves of the associated
+/// testcase, as collected by [`PrefixSnapshotFeedback`].
+///
+/// Checkpoint `i` is the board after the first `i * interval` moves were applied, so the state after
+/// any prefix is at most `interval - 1` moves away from a stored one.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PrefixSnapshotMetadata<T> {
+    interval: NonZeroUsize,
+    moves: usize,
+    checkpoints: Vec<State<T>>,
+}
+
+impl<T> PrefixSnapshotMetadata<T>
+where
+    T: BoardValue,
+{
+    /// The number of moves of the testcase these checkpoints were taken from.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    /// The state after the first `prefix` of the provided moves, which must be those of the
+    /// testcase the checkpoints were taken from. Returns [`None`] if the input is not that long.
+    pub fn state(
+        &self,
+        moves: &[(NonZeroUsize, Direction)],
+        prefix: usize,
+    ) -> Result<Option<State<T>>, Error> {
+        if prefix > self.moves || moves.len() != self.moves {
+            return Ok(None);
+        }
+        let from = prefix / self.interval.get();
+        let mut state = self.checkpoints[from].clone();
+        let mut board = state
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+        for &(car, dir) in &moves[from * self.interval.get()..prefix] {
+            board
+                .shift_car(car, dir)
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+        }
+        drop(board);
+        Ok(Some(state))
+    }
+}
+
+impl_serdeany!(PrefixSnapshotMetadata<T: BoardValue + DeserializeOwned + Serialize + 'static>, <u8>, <u16>);
+
+impl<EM, OT, S, T> Feedback<EM, PGInput, OT, S> for PrefixSnapshotFeedback<T>
+where
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &PGInput,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<PGInput>,
+    ) -> Result<(), Error> {
+        let moves = testcase
+            .input()
+            .as_ref()
+            .ok_or_else(|| Error::empty_optional("testcase has no input to replay"))?
+            .moves();
+
+        let mut replay = self.initial.clone();
+        let mut board = replay
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+
+        let mut checkpoints = Vec::with_capacity(moves.len() / self.interval.get() + 1);
+        checkpoints.push(board.state().clone());
+
+        for (i, &(car, dir)) in moves.iter().enumerate() {
+            board
+                .shift_car(car, dir)
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+            if (i + 1) % self.interval.get() == 0 {
+                checkpoints.push(board.state().clone());
+            }
+        }
+
+        drop(board);
+        let metadata = PrefixSnapshotMetadata {
+            interval: self.interval,
+            moves: moves.len(),
+            checkpoints,
+        };
+        testcase.add_metadata(metadata);
+        Ok(())
+    }
+}
+
 /// Feedback which interprets the view data from [`ViewObserver`] to determine if the board is in a
 /// solved state (i.e., when the objective vehicle sees the wall).
 pub struct SolvedFeedback<T> {
@@ -202,6 +363,350 @@ where
     }
 }
 
+/// Feedback which interprets the heuristic from [`BlockerObserver`] to direct the fuzzer towards
+/// the solution: a testcase is interesting if it gets closer to the solution than any before it.
+///
+/// The heuristic of each testcase is stashed in [`BlockerMetadata`] for later use, e.g., by
+/// schedulers.
+pub struct BlockerFeedback {
+    obs: Handle<BlockerObserver>,
+}
+
+impl BlockerFeedback {
+    /// Create a [`BlockerFeedback`] which will interpret the result from the [`BlockerObserver`].
+    pub fn new(obs: &BlockerObserver) -> Self {
+        Self { obs: obs.handle() }
+    }
+}
+
+/// Metadata which tracks the lowest heuristic seen by the fuzzer so far.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct BlockerMinimumMetadata {
+    minimum: Option<usize>,
+}
+
+impl BlockerMinimumMetadata {
+    /// The lowest heuristic seen so far, or [`None`] if nothing has completed normally yet.
+    pub fn minimum(&self) -> Option<usize> {
+        self.minimum
+    }
+}
+
+impl_serdeany!(BlockerMinimumMetadata);
+
+/// Metadata which holds the blocker measurements of the final board of the associated testcase.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BlockerMetadata {
+    blockers: usize,
+    depth: usize,
+}
+
+impl BlockerMetadata {
+    /// Create new [`BlockerMetadata`] with the provided measurements.
+    pub fn new(blockers: usize, depth: usize) -> Self {
+        Self { blockers, depth }
+    }
+
+    /// The number of cars directly between the objective car and the exit.
+    pub fn blockers(&self) -> usize {
+        self.blockers
+    }
+
+    /// The depth of the blocker chain.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The heuristic distance from the solution; see [`BlockerObserver::heuristic`].
+    pub fn heuristic(&self) -> usize {
+        self.blockers + self.depth
+    }
+}
+
+impl_serdeany!(BlockerMetadata);
+
+impl<S> StateInitializer<S> for BlockerFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_metadata(BlockerMinimumMetadata::default());
+        Ok(())
+    }
+}
+
+impl Named for BlockerFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_blockers_fb");
+        &NAME
+    }
+}
+
+impl<EM, OT, S> Feedback<EM, PGInput, OT, S> for BlockerFeedback
+where
+    EM: EventFirer<PGInput, S>,
+    OT: MatchNameRef,
+    S: HasMetadata + HasExecutions,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &PGInput,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let Some(heuristic) = observers.get(&self.obs).unwrap().heuristic() else {
+            return Ok(false);
+        };
+
+        let meta = state.metadata_mut::<BlockerMinimumMetadata>()?;
+        if meta.minimum.is_some_and(|minimum| minimum <= heuristic) {
+            return Ok(false);
+        }
+        meta.minimum = Some(heuristic);
+
+        let executions = *state.executions();
+        manager.fire(
+            state,
+            EventWithStats::new(
+                Event::UpdateUserStats {
+                    name: self.name().clone(),
+                    value: UserStats::new(
+                        UserStatsValue::Number(heuristic as u64),
+                        AggregatorOps::Min, // if aggregated, report the closest anyone got
+                    ),
+                    phantom: PhantomData,
+                },
+                ExecStats::new(current_time(), executions),
+            ),
+        )?;
+
+        Ok(true)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<PGInput>,
+    ) -> Result<(), Error> {
+        let obs = observers.get(&self.obs).unwrap();
+        if let (Some(blockers), Some(depth)) = (obs.blockers(), obs.depth()) {
+            testcase.add_metadata(BlockerMetadata { blockers, depth });
+        }
+        Ok(())
+    }
+}
+
+/// Feedback which records the mobility of the final board of each new testcase, as measured by the
+/// [`MobilityObserver`], so that schedulers may prioritise testcases with more unexplored
+/// successors. Never marks an execution as interesting by itself.
+pub struct MobilityFeedback {
+    obs: Handle<MobilityObserver>,
+}
+
+impl MobilityFeedback {
+    /// Create a [`MobilityFeedback`] which will interpret the result from the [`MobilityObserver`].
+    pub fn new(obs: &MobilityObserver) -> Self {
+        Self { obs: obs.handle() }
+    }
+}
+
+/// Metadata which holds the mobility of the final board of the associated testcase.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MobilityMetadata {
+    moves: usize,
+    movable: usize,
+}
+
+impl MobilityMetadata {
+    /// The number of legal (car, direction, distance) moves on the final board.
+    pub fn moves(&self) -> usize {
+        self.moves
+    }
+
+    /// The number of cars which could move at all on the final board.
+    pub fn movable(&self) -> usize {
+        self.movable
+    }
+}
+
+impl_serdeany!(MobilityMetadata);
+
+impl<S> StateInitializer<S> for MobilityFeedback {}
+
+impl Named for MobilityFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_mobility_fb");
+        &NAME
+    }
+}
+
+impl<EM, OT, S> Feedback<EM, PGInput, OT, S> for MobilityFeedback
+where
+    OT: MatchNameRef,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &PGInput,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        observers: &OT,
+        testcase: &mut Testcase<PGInput>,
+    ) -> Result<(), Error> {
+        let obs = observers.get(&self.obs).unwrap();
+        if let (Some(moves), Some(movable)) = (obs.moves(), obs.movable()) {
+            testcase.add_metadata(MobilityMetadata { moves, movable });
+        }
+        Ok(())
+    }
+}
+
+/// Feedback which marks an execution as interesting if it reaches a state whose hash has not been
+/// seen before, like [`libafl::feedbacks::NewHashFeedback`].
+///
+/// Optionally, the encoding of each state (see [`StateEncoding`]) is stored alongside its hash. A
+/// state whose hash was seen before but whose encoding was not is then recognised as new rather than
+/// silently dropped, and the number of such hash collisions is reported as a user stat. This costs
+/// a few bytes per car for every state seen.
+pub struct StateNoveltyFeedback<O> {
+    obs: Handle<O>,
+    verify: bool,
+}
+
+impl<O> StateNoveltyFeedback<O>
+where
+    O: Named,
+{
+    /// Create a [`StateNoveltyFeedback`] which will interpret the hash from the provided observer,
+    /// and verify it against the state encoding if `verify` is set.
+    pub fn new(obs: &O, verify: bool) -> Self {
+        Self {
+            obs: obs.handle(),
+            verify,
+        }
+    }
+}
+
+/// Metadata which tracks the hashes (and, if verifying, encodings) of the states seen so far.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct StateNoveltyMetadata {
+    seen: HashMap<u64, Vec<Vec<u8>>>,
+    collisions: u64,
+}
+
+impl StateNoveltyMetadata {
+    /// Records the provided state hash and encoding, if provided. Returns true if the state had not
+    /// been seen before.
+    pub fn observe(&mut self, hash: u64, encoding: Option<Vec<u8>>) -> bool {
+        let encodings = match self.seen.get_mut(&hash) {
+            None => {
+                self.seen.insert(hash, encoding.into_iter().collect());
+                return true;
+            }
+            Some(encodings) => encodings,
+        };
+        match encoding {
+            // a hash recorded without verification can't be checked
+            Some(encoding) if !encodings.is_empty() && !encodings.contains(&encoding) => {
+                encodings.push(encoding);
+                self.collisions += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// The number of distinct hashes seen so far.
+    pub fn hashes(&self) -> usize {
+        self.seen.len()
+    }
+
+    /// The number of hash collisions detected so far.
+    pub fn collisions(&self) -> u64 {
+        self.collisions
+    }
+}
+
+impl_serdeany!(StateNoveltyMetadata);
+
+impl<O, S> StateInitializer<S> for StateNoveltyFeedback<O>
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_metadata(StateNoveltyMetadata::default());
+        Ok(())
+    }
+}
+
+impl<O> Named for StateNoveltyFeedback<O> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_state_novelty");
+        &NAME
+    }
+}
+
+impl<EM, I, O, OT, S> Feedback<EM, I, OT, S> for StateNoveltyFeedback<O>
+where
+    EM: EventFirer<I, S>,
+    O: ObserverWithHashField + StateEncoding,
+    OT: MatchNameRef,
+    S: HasMetadata + HasExecutions,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let obs = observers.get(&self.obs).unwrap();
+        let Some(hash) = obs.hash() else {
+            return Ok(false);
+        };
+        let encoding = if self.verify { obs.encoding() } else { None };
+
+        let meta = state.metadata_mut::<StateNoveltyMetadata>()?;
+        let collisions = meta.collisions;
+        let novel = meta.observe(hash, encoding);
+
+        if meta.collisions != collisions {
+            let collisions = meta.collisions;
+            let executions = *state.executions();
+            manager.fire(
+                state,
+                EventWithStats::new(
+                    Event::UpdateUserStats {
+                        name: Cow::Borrowed("pg_hash_collisions"),
+                        value: UserStats::new(
+                            UserStatsValue::Number(collisions),
+                            AggregatorOps::Sum, // if aggregated, report the total
+                        ),
+                        phantom: PhantomData,
+                    },
+                    ExecStats::new(current_time(), executions),
+                ),
+            )?;
+        }
+
+        Ok(novel)
+    }
+}
+
 /// Feedback which measures and reports the crash rate of the executor.
 pub struct CrashRateFeedback;
 
@@ -281,19 +786,350 @@ where
     }
 }
 
+/// Feedback which breaks crashes down by the kind of illegal move that caused them, as reported by
+/// the [`IllegalMoveObserver`]. Use alongside [`CrashRateFeedback`], which reports the overall rate.
+pub struct CrashCauseFeedback {
+    obs: Handle<IllegalMoveObserver>,
+}
+
+impl CrashCauseFeedback {
+    /// Create a [`CrashCauseFeedback`] which will interpret the result from the
+    /// [`IllegalMoveObserver`].
+    pub fn new(obs: &IllegalMoveObserver) -> Self {
+        Self { obs: obs.handle() }
+    }
+}
+
+/// Metadata which tracks how many crashes were caused by each kind of illegal move, and which move
+/// crashed the last execution.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct CrashCauseMetadata {
+    crashes: [u64; IllegalMoveKind::ALL.len()],
+    last: Option<IllegalMove>,
+}
+
+impl CrashCauseMetadata {
+    /// The number of crashes caused by the provided kind of illegal move (regardless of which car
+    /// was intersected with, for [`IllegalMoveKind::Intersects`]).
+    pub fn crashes(&self, kind: IllegalMoveKind) -> u64 {
+        self.crashes[kind.ordinal()]
+    }
+
+    /// The illegal move which crashed the last execution, or [`None`] if it didn't crash. Crashing
+    /// inputs never enter the corpus, so this is the only place to find out where they went wrong;
+    /// the input may be repaired by truncating it to [`IllegalMove::index`].
+    pub fn last(&self) -> Option<&IllegalMove> {
+        self.last.as_ref()
+    }
+}
+
+impl_serdeany!(CrashCauseMetadata);
+
+impl<S> StateInitializer<S> for CrashCauseFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.add_metadata(CrashCauseMetadata::default());
+        Ok(())
+    }
+}
+
+impl Named for CrashCauseFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_crash_cause");
+        &NAME
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashCauseFeedback
+where
+    EM: EventFirer<I, S>,
+    OT: MatchNameRef,
+    S: HasMetadata + HasExecutions,
+{
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let illegal = observers.get(&self.obs).unwrap().illegal().copied();
+        let meta = state.metadata_mut::<CrashCauseMetadata>()?;
+        if let Some(illegal) = illegal {
+            meta.crashes[illegal.kind().ordinal()] += 1;
+        }
+        meta.last = illegal;
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let crashes = state.metadata::<CrashCauseMetadata>()?.crashes;
+        let executions = *state.executions();
+        for kind in IllegalMoveKind::ALL {
+            manager.fire(
+                state,
+                EventWithStats::new(
+                    Event::UpdateUserStats {
+                        name: Cow::Owned(format!("{}_{}", self.name(), kind.name())),
+                        value: UserStats::new(
+                            UserStatsValue::Ratio(crashes[kind.ordinal()], executions),
+                            AggregatorOps::Avg, // if aggregated, report the average number
+                        ),
+                        phantom: PhantomData,
+                    },
+                    ExecStats::new(current_time(), executions),
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Feedback which reports the per-mutator success rates tracked by
+/// [`crate::mutators::PGAdaptiveMutator`] as user stats.
+pub struct MutatorStatsFeedback;
+
+impl<S> StateInitializer<S> for MutatorStatsFeedback {}
+
+impl Named for MutatorStatsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_mutator_stats");
+        &NAME
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for MutatorStatsFeedback
+where
+    EM: EventFirer<I, S>,
+    S: HasMetadata + HasExecutions,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Ok(meta) = state.metadata::<MutatorStatsMetadata>() else {
+            // the adaptive mutator hasn't mutated anything yet
+            return Ok(());
+        };
+        let stats = meta
+            .stats()
+            .map(|(name, uses, finds)| (format!("{}_{name}", self.name()), uses, finds))
+            .collect::<Vec<_>>();
+        let executions = *state.executions();
+
+        for (name, uses, finds) in stats {
+            manager.fire(
+                state,
+                EventWithStats::new(
+                    Event::UpdateUserStats {
+                        name: Cow::Owned(name),
+                        value: UserStats::new(
+                            UserStatsValue::Ratio(finds, uses),
+                            AggregatorOps::Avg,
+                        ),
+                        phantom: PhantomData,
+                    },
+                    ExecStats::new(current_time(), executions),
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Feedback which reports the number of exhausted and frontier testcases tracked by
+/// [`crate::schedulers::RetiringScheduler`] as user stats.
+pub struct RetirementStatsFeedback;
+
+impl<S> StateInitializer<S> for RetirementStatsFeedback {}
+
+impl Named for RetirementStatsFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_retirement");
+        &NAME
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for RetirementStatsFeedback
+where
+    EM: EventFirer<I, S>,
+    S: HasMetadata + HasExecutions,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _observers: &OT,
+        _testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Ok(meta) = state.metadata::<RetirementMetadata>() else {
+            // the retiring scheduler hasn't scheduled anything yet
+            return Ok(());
+        };
+        let stats = [
+            ("pg_exhausted", meta.exhausted()),
+            ("pg_frontier", meta.frontier()),
+        ];
+        let executions = *state.executions();
+
+        for (name, value) in stats {
+            manager.fire(
+                state,
+                EventWithStats::new(
+                    Event::UpdateUserStats {
+                        name: Cow::Borrowed(name),
+                        value: UserStats::new(
+                            UserStatsValue::Number(value as u64),
+                            AggregatorOps::Sum, // if aggregated, report the total
+                        ),
+                        phantom: PhantomData,
+                    },
+                    ExecStats::new(current_time(), executions),
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Feedback which learns the recurring move sequences of every new corpus entry or solution into
+/// the [`MacroDictionaryMetadata`] used by [`crate::mutators::PGMacroMutator`]. Only the sequences
+/// which end in the moves that differ from the testcase it was mutated from are learned, so that
+/// the shared prefix is not counted again for every descendant. Never marks an execution as
+/// interesting by itself, so use it in both the feedback and the objective.
+pub struct MacroFeedback {
+    max_len: usize,
+}
+
+impl MacroFeedback {
+    /// Create a [`MacroFeedback`] which learns sequences of up to `max_len` moves.
+    pub fn new(max_len: usize) -> Self {
+        assert!(max_len >= 2, "macros must have at least two moves");
+        Self { max_len }
+    }
+}
+
+impl<S> StateInitializer<S> for MacroFeedback
+where
+    S: HasMetadata,
+{
+    fn init_state(&mut self, state: &mut S) -> Result<(), Error> {
+        state.metadata_or_insert_with(MacroDictionaryMetadata::default);
+        Ok(())
+    }
+}
+
+impl Named for MacroFeedback {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_macro_fb");
+        &NAME
+    }
+}
+
+impl<EM, OT, S> Feedback<EM, PGInput, OT, S> for MacroFeedback
+where
+    S: HasCorpus<PGInput> + HasMetadata,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &PGInput,
+        _observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<PGInput>,
+    ) -> Result<(), Error> {
+        let input = testcase
+            .input()
+            .as_ref()
+            .ok_or_else(|| Error::empty_optional("testcase has no input to learn from"))?;
+        // the moves shared with the testcase this was mutated from were learned with that one
+        let known = testcase
+            .parent_id()
+            .or(*state.corpus().current())
+            .and_then(|id| state.corpus().cloned_input_for_id(id).ok())
+            .map_or(0, |parent| {
+                parent
+                    .moves()
+                    .iter()
+                    .zip(input.moves())
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+        state
+            .metadata_or_insert_with(MacroDictionaryMetadata::default)
+            .learn(input.moves(), known, self.max_len);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::feedbacks::SolvedFeedback;
+    use crate::feedbacks::{
+        BlockerFeedback, CrashCauseFeedback, CrashCauseMetadata, SolvedFeedback,
+        StateNoveltyMetadata,
+    };
     use crate::input::PGInput;
-    use crate::observers::{PGObserverTuple, View, ViewObserver};
+    use crate::observers::{
+        BlockerObserver, IllegalMoveKind, IllegalMoveObserver, PGObserverTuple, View, ViewObserver,
+        encode_state,
+    };
+    use libafl::HasMetadata;
     use libafl::events::SimpleEventManager;
     use libafl::executors::ExitKind;
-    use libafl::feedbacks::Feedback;
+    use libafl::feedbacks::{Feedback, StateInitializer};
     use libafl::observers::ObserversTuple;
     use libafl::state::NopState;
     use libafl_bolts::tuples::tuple_list;
     use parking_game::Direction;
     use std::error::Error;
+    use std::num::NonZeroUsize;
 
     #[test]
     fn simple_solved() -> Result<(), Box<dyn Error>> {
@@ -387,4 +1223,108 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn blocker_minimum() -> Result<(), Box<dyn Error>> {
+        let obs = BlockerObserver::default();
+        let mut blockers = BlockerFeedback::new(&obs);
+
+        let mut observers = tuple_list!(ViewObserver::<u8>::default(), obs);
+
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+        blockers.init_state(&mut state)?;
+
+        let nop_input = PGInput::new(vec![]);
+        for (map, interesting) in [
+            // 2 is in the way, and is blocked by 3
+            ("oo2\n..2\n.33", true),
+            // no better than before
+            ("oo2\n..2\n.33", false),
+            // 2 is in the way, but can move out of it freely
+            ("oo2\n..2\n33.", true),
+            // nothing in the way
+            ("oo.\n..2\n.32\n.3.", true),
+        ] {
+            let initial = crate::parse_map::<u8>(map);
+            observers.pre_exec_all(&mut state, &nop_input)?;
+            observers.final_board_all(&initial.board()?);
+            observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+
+            assert_eq!(
+                interesting,
+                blockers.is_interesting(
+                    &mut state,
+                    &mut mgr,
+                    &nop_input,
+                    &observers,
+                    &ExitKind::Ok
+                )?
+            );
+        }
+        assert_eq!(Some(0), observers.1.0.heuristic());
+
+        Ok(())
+    }
+
+    #[test]
+    fn crash_cause() -> Result<(), Box<dyn Error>> {
+        let mut initial = crate::parse_map::<u8>("oo2\n..2");
+        let obs = IllegalMoveObserver::default();
+        let mut causes = CrashCauseFeedback::new(&obs);
+
+        let mut observers = tuple_list!(obs);
+
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+        causes.init_state(&mut state)?;
+
+        let car = NonZeroUsize::new(2).unwrap();
+        let input = PGInput::new(vec![(car, Direction::Down)]);
+        observers.pre_exec_all(&mut state, &input)?;
+        let board = initial.board_mut()?;
+        // the car is already at the bottom, so it can't move down
+        observers.illegal_move_all(0, car, Direction::Down, &board);
+        observers.post_exec_all(&mut state, &input, &ExitKind::Crash)?;
+        assert!(!causes.is_interesting(
+            &mut state,
+            &mut mgr,
+            &input,
+            &observers,
+            &ExitKind::Crash
+        )?);
+
+        let meta = state.metadata::<CrashCauseMetadata>()?;
+        assert_eq!(0, meta.last().unwrap().index());
+        assert_eq!(1, meta.crashes(IllegalMoveKind::InvalidFinalPosition));
+
+        // the next execution doesn't crash, so there is nothing to repair
+        let nop_input = PGInput::new(vec![]);
+        observers.pre_exec_all(&mut state, &nop_input)?;
+        observers.final_board_all(&initial.board()?);
+        observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+        causes.is_interesting(&mut state, &mut mgr, &nop_input, &observers, &ExitKind::Ok)?;
+        assert!(state.metadata::<CrashCauseMetadata>()?.last().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn hash_collision() {
+        let first = encode_state(&crate::parse_map::<u8>("oo..\n22.."));
+        let second = encode_state(&crate::parse_map::<u8>("oo..\n.22."));
+        assert_ne!(first, second);
+
+        let mut meta = StateNoveltyMetadata::default();
+        assert!(meta.observe(0, Some(first.clone())));
+        assert!(!meta.observe(0, Some(first)));
+        // same hash, different state: without the encodings, this would have been dropped
+        assert!(meta.observe(0, Some(second.clone())));
+        assert!(!meta.observe(0, Some(second)));
+        assert!(meta.observe(1, None));
+        assert!(!meta.observe(1, None));
+
+        assert_eq!(2, meta.hashes());
+        assert_eq!(1, meta.collisions());
+    }
 }
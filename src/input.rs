@@ -24,6 +24,16 @@ impl PGInput {
         &self.moves
     }
 
+    /// The number of slides in this input, where a slide is a run of consecutive moves of the same
+    /// car in the same direction (i.e., what a player would count as a single move).
+    pub fn slides(&self) -> usize {
+        self.moves
+            .iter()
+            .enumerate()
+            .filter(|&(i, m)| i == 0 || self.moves[i - 1] != *m)
+            .count()
+    }
+
     /// A mutable reference to the sequence of moves in this input, for use in mutators.
     pub fn moves_mut(&mut self) -> &mut Vec<(NonZeroUsize, Direction)> {
         &mut self.moves
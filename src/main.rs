@@ -1,11 +1,16 @@
 //! Fuzzer for [`parking_game`] puzzles. This is meant as an exercise for learning how to use
 //! LibAFL, and potentially not great for "real" applications, if they exist.
 
+pub mod bitboard;
 pub mod executor;
 pub mod feedbacks;
 pub mod input;
 pub mod mutators;
 pub mod observers;
+pub mod parallel;
+pub mod persist;
+pub mod schedulers;
+pub mod snapshots;
 pub mod stages;
 
 use crate::input::PGInput;
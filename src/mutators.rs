@@ -1,18 +1,22 @@
 //! Mutators for [`PGInput`]s -- so you can fuzz [`parking_game`] puzzles!
 
+use crate::feedbacks::{FinalStateMetadata, PrefixSnapshotMetadata, ViewMetadata};
 use crate::input::PGInput;
-use libafl::Error;
-use libafl::corpus::CorpusId;
-use libafl::mutators::{MutationResult, Mutator};
-use libafl::state::{HasCurrentTestcase, HasRand};
-use libafl_bolts::Named;
+use crate::observers::{ViewFrom, blocker_chain, views_of};
+use libafl::corpus::{Corpus, CorpusId};
+use libafl::mutators::{ComposedByMutations, MutationResult, Mutator, MutatorsTuple};
+use libafl::state::{HasCurrentTestcase, HasRand, HasSolutions};
+use libafl::{Error, HasMetadata};
 use libafl_bolts::rands::Rand;
-use parking_game::{BoardValue, State};
-use serde::Serialize;
+use libafl_bolts::tuples::{HasConstLen, NamedTuple};
+use libafl_bolts::{Named, impl_serdeany};
+use parking_game::{BoardValue, Direction, State};
 use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
-use std::num::NonZeroUsize;
+use std::num::{NonZeroU64, NonZeroUsize};
 
 /// Randomly mutate the moves -- at any point with anything.
 ///
@@ -70,19 +74,171 @@ where
     }
 }
 
+/// How [`PGTailMutator`] weights the valid moves it may append to the input.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum TailPolicy {
+    /// Every (car, direction, distance) option is equally likely. Cars with long free runs are
+    /// picked more often, as they have more options.
+    #[default]
+    Uniform,
+    /// Every car which can move is equally likely, then every option for that car.
+    UniformCars,
+    /// Cars are more likely to be picked the longer it has been since they were last moved.
+    Unmoved,
+    /// The objective car and the cars in its [`blocker_chain`] are more likely to be picked.
+    /// Requires [`FinalStateMetadata`] to be available on the mutated testcase.
+    Blockers,
+    /// Every option is equally likely, except for the one which undoes the last move.
+    NoReversal,
+}
+
+/// How many times more likely [`TailPolicy::Blockers`] is to pick the objective car or one of its
+/// blockers than any other car.
+const BLOCKER_WEIGHT: usize = 4;
+
+/// The views from each car on the final board of the current testcase, along with whether each car
+/// is the objective car or in its [`blocker_chain`], in car order. Requires both
+/// [`crate::feedbacks::ViewMetadata`] and [`FinalStateMetadata`] to be available on the testcase.
+fn blocker_targets<S, T>(state: &S) -> Result<(Vec<ViewFrom<T>>, Vec<bool>), Error>
+where
+    S: HasCurrentTestcase<PGInput>,
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    let testcase = state.current_testcase()?;
+    let views = testcase
+        .metadata::<ViewMetadata<T>>()?
+        .views()
+        .map(|(_, view)| *view)
+        .collect::<Vec<_>>();
+    let board = testcase
+        .metadata::<FinalStateMetadata<T>>()?
+        .state()
+        .board()
+        .map_err(|e| Error::illegal_state(e.to_string()))?;
+
+    let mut targets = vec![false; views.len()];
+    targets[0] = true;
+    for (blocker, _) in blocker_chain(&board, &views) {
+        targets[blocker.get() - 1] = true;
+    }
+    Ok((views, targets))
+}
+
 /// Mutator which adds a _valid_ move to the end of the sequence. Only valid when used as the only
 /// mutator and when [`crate::feedbacks::ViewMetadata`] is available on the mutated testcase.
 pub struct PGTailMutator<T> {
+    policy: TailPolicy,
     phantom: PhantomData<T>,
 }
 
 impl<T> PGTailMutator<T> {
     /// Create a new mutator for the provided state.
-    pub fn new(_state: &State<T>) -> Self {
+    pub fn new(state: &State<T>) -> Self {
+        Self::with_policy(state, TailPolicy::default())
+    }
+
+    /// Create a new mutator for the provided state which weights its options by the provided
+    /// policy.
+    pub fn with_policy(_state: &State<T>, policy: TailPolicy) -> Self {
         Self {
+            policy,
             phantom: PhantomData,
         }
     }
+
+    /// The policy with which this mutator weights its options.
+    pub fn policy(&self) -> TailPolicy {
+        self.policy
+    }
+}
+
+impl<T> PGTailMutator<T>
+where
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    /// Select one of the provided (car, direction, distance) options, weighted by this mutator's
+    /// [`TailPolicy`], or [`None`] if there are no options.
+    pub fn select<S>(
+        &self,
+        state: &mut S,
+        input: &PGInput,
+        mut choices: Vec<(NonZeroUsize, Direction, T)>,
+    ) -> Result<Option<(NonZeroUsize, Direction, T)>, Error>
+    where
+        S: HasRand + HasCurrentTestcase<PGInput>,
+    {
+        let weights = match self.policy {
+            TailPolicy::Uniform => vec![1; choices.len()],
+            TailPolicy::UniformCars => {
+                // pick the car first, then only give weight to its options
+                let mut cars = choices.iter().map(|&(car, _, _)| car).collect::<Vec<_>>();
+                cars.sort_unstable();
+                cars.dedup();
+                let Some(selected) = state.rand_mut().choose(cars) else {
+                    return Ok(None);
+                };
+                choices
+                    .iter()
+                    .map(|&(car, _, _)| usize::from(car == selected))
+                    .collect()
+            }
+            TailPolicy::Unmoved => choices
+                .iter()
+                .map(|&(car, _, _)| {
+                    input
+                        .moves()
+                        .iter()
+                        .rev()
+                        .position(|&(moved, _)| moved == car)
+                        .unwrap_or(input.moves().len())
+                        + 1
+                })
+                .collect(),
+            TailPolicy::Blockers => {
+                let (_, targets) = blocker_targets::<_, T>(state)?;
+                choices
+                    .iter()
+                    .map(|&(car, _, _)| {
+                        if targets[car.get() - 1] {
+                            BLOCKER_WEIGHT
+                        } else {
+                            1
+                        }
+                    })
+                    .collect()
+            }
+            TailPolicy::NoReversal => {
+                let reversal = input.moves().last().map(|&(car, dir)| (car, -dir));
+                let weights = choices
+                    .iter()
+                    .map(|&(car, dir, _)| usize::from(Some((car, dir)) != reversal))
+                    .collect::<Vec<_>>();
+                if weights.contains(&1) {
+                    weights
+                } else {
+                    // the only way to go is back
+                    vec![1; choices.len()]
+                }
+            }
+        };
+
+        let Some(total) = NonZeroUsize::new(weights.iter().sum()) else {
+            return Ok(None);
+        };
+        let mut remaining = state.rand_mut().below(total);
+        let idx = weights
+            .iter()
+            .position(|&weight| {
+                if remaining < weight {
+                    true
+                } else {
+                    remaining -= weight;
+                    false
+                }
+            })
+            .unwrap();
+        Ok(Some(choices.swap_remove(idx)))
+    }
 }
 
 impl<T> Named for PGTailMutator<T> {
@@ -107,8 +263,9 @@ where
         //      decrement it with `-= T::one()`
         //      - remember not to mutate the metadata in place! this will affect future iterations
         //    - `drop(...)` the testcase after use so that you can mutably use the state again
-        //  - finally, select from this list randomly with `state.rand_mut().choose(...)` and apply
-        //    the mutation with `.push()` (potentially multiple times for `T > 1`)
+        //  - finally, select from this list with `self.select(...)` (which weights the options by
+        //    the policy this mutator was created with) and apply the mutation with `.push()`
+        //    (potentially multiple times for `T > 1`)
 
         todo!("Indicate that the input was mutated")
     }
@@ -118,3 +275,915 @@ where
         Ok(())
     }
 }
+
+/// Mutator which inserts a _valid_ move at any point in the sequence, then repairs the moves after
+/// it by dropping those which are no longer valid. Requires
+/// [`crate::feedbacks::PrefixSnapshotMetadata`] to be available on the mutated testcase.
+///
+/// Unlike [`PGTailMutator`], this can reach inputs which differ from the testcase early on, e.g. by
+/// moving a car out of the way before a sequence of moves which previously needed to go around it.
+pub struct PGInsertMutator<T> {
+    phantom: PhantomData<T>,
+}
+
+impl<T> PGInsertMutator<T> {
+    /// Create a new mutator for the provided state.
+    pub fn new(_state: &State<T>) -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Named for PGInsertMutator<T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_insert");
+        &NAME
+    }
+}
+
+impl<S, T> Mutator<PGInput, S> for PGInsertMutator<T>
+where
+    S: HasRand + HasCurrentTestcase<PGInput>,
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PGInput) -> Result<MutationResult, Error> {
+        // any index can be selected, including the end
+        let at = state
+            .rand_mut()
+            .below(NonZeroUsize::new(input.moves().len() + 1).unwrap());
+
+        let testcase = state.current_testcase()?;
+        if testcase.input().as_ref().map(PGInput::moves) != Some(input.moves()) {
+            // the input was already changed by another mutator; the snapshots don't describe it
+            return Ok(MutationResult::Skipped);
+        }
+        let meta = testcase.metadata::<PrefixSnapshotMetadata<T>>()?;
+        let Some(mut snapshot) = meta.state(input.moves(), at)? else {
+            return Ok(MutationResult::Skipped);
+        };
+        drop(testcase);
+
+        let mut choices = Vec::new();
+        {
+            let board = snapshot
+                .board()
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+            for (i, view) in views_of(&board).enumerate() {
+                let car = NonZeroUsize::new(i + 1).unwrap();
+                for view in [view.backward(), view.forward()] {
+                    let mut distance = *view.distance();
+                    while !distance.is_zero() {
+                        choices.push((car, view.direction(), distance));
+                        distance -= T::one();
+                    }
+                }
+            }
+        }
+
+        let Some((car, dir, distance)) = state.rand_mut().choose(choices) else {
+            // every car is stuck at this point
+            return Ok(MutationResult::Skipped);
+        };
+
+        let mut board = snapshot
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+        let suffix = input.moves_mut().split_off(at);
+
+        let mut remaining = distance;
+        while !remaining.is_zero() {
+            board
+                .shift_car(car, dir)
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+            input.moves_mut().push((car, dir));
+            remaining -= T::one();
+        }
+
+        // repair: keep only the moves which are still valid after the insertion
+        for (car, dir) in suffix {
+            if board.shift_car(car, dir).is_ok() {
+                input.moves_mut().push((car, dir));
+            }
+        }
+
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Mutator which adds a _valid_ move to the end of the sequence, preferring to move the objective
+/// car or the cars which block it (see [`blocker_chain`]). Requires both
+/// [`crate::feedbacks::ViewMetadata`] and [`FinalStateMetadata`] to be available on the mutated
+/// testcase.
+///
+/// Only directing the search this way would get stuck on boards where the blockers must be moved
+/// "the long way round", so a random valid move is still chosen with some probability.
+pub struct PGBlockerMutator<T> {
+    directed: f64,
+    phantom: PhantomData<T>,
+}
+
+impl<T> PGBlockerMutator<T> {
+    /// Create a new mutator for the provided state, which picks a move of a car in the blocker
+    /// chain with probability `directed` (between 0 and 1) and a random valid move otherwise.
+    pub fn new(_state: &State<T>, directed: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&directed),
+            "directed must be a probability"
+        );
+        Self {
+            directed,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> Named for PGBlockerMutator<T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_blocker");
+        &NAME
+    }
+}
+
+impl<S, T> Mutator<PGInput, S> for PGBlockerMutator<T>
+where
+    S: HasRand + HasCurrentTestcase<PGInput>,
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PGInput) -> Result<MutationResult, Error> {
+        let (views, targets) = blocker_targets::<_, T>(state)?;
+
+        let mut all = Vec::new();
+        let mut directed = Vec::new();
+        for (idx, view) in views.iter().enumerate() {
+            let car = NonZeroUsize::new(idx + 1).unwrap();
+            let in_chain = targets[idx];
+            for view in [view.backward(), view.forward()] {
+                let mut distance = *view.distance();
+                while !distance.is_zero() {
+                    all.push((car, view.direction(), distance));
+                    if in_chain {
+                        directed.push((car, view.direction(), distance));
+                    }
+                    distance -= T::one();
+                }
+            }
+        }
+
+        let choices = if !directed.is_empty() && state.rand_mut().coinflip(self.directed) {
+            directed
+        } else {
+            all
+        };
+        let Some((car, dir, mut distance)) = state.rand_mut().choose(choices) else {
+            // every car is stuck
+            return Ok(MutationResult::Skipped);
+        };
+
+        while !distance.is_zero() {
+            input.moves_mut().push((car, dir));
+            distance -= T::one();
+        }
+
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The minimum probability with which [`PGAdaptiveMutator`] picks any of its mutators, so that no
+/// mutator is ever starved out entirely.
+const MIN_PROBABILITY: f64 = 0.02;
+
+/// The inertia of the probabilities updated by [`PGAdaptiveMutator`], i.e. how much of the previous
+/// change in probability carries over into the next update.
+const INERTIA: f64 = 0.7;
+
+/// Metadata which tracks how often each of the mutators of a [`PGAdaptiveMutator`] was used and how
+/// often that led to a new corpus entry or solution, along with the resulting probabilities.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MutatorStatsMetadata {
+    names: Vec<String>,
+    uses: Vec<u64>,
+    finds: Vec<u64>,
+    period_uses: Vec<u64>,
+    period_finds: Vec<u64>,
+    probabilities: Vec<f64>,
+    velocities: Vec<f64>,
+    best_probabilities: Vec<f64>,
+    best_efficiencies: Vec<f64>,
+}
+
+impl_serdeany!(MutatorStatsMetadata);
+
+impl MutatorStatsMetadata {
+    fn new(names: Vec<String>) -> Self {
+        let count = names.len();
+        let uniform = 1.0 / count as f64;
+        Self {
+            names,
+            uses: vec![0; count],
+            finds: vec![0; count],
+            period_uses: vec![0; count],
+            period_finds: vec![0; count],
+            probabilities: vec![uniform; count],
+            velocities: vec![0.0; count],
+            best_probabilities: vec![uniform; count],
+            best_efficiencies: vec![0.0; count],
+        }
+    }
+
+    /// An iterator over the name of each mutator, how often it was used, and how many of those uses
+    /// led to a new corpus entry or solution.
+    pub fn stats(&self) -> impl Iterator<Item = (&str, u64, u64)> {
+        self.names
+            .iter()
+            .zip(self.uses.iter().zip(self.finds.iter()))
+            .map(|(name, (&uses, &finds))| (name.as_str(), uses, finds))
+    }
+
+    /// The probability with which each mutator is currently selected.
+    pub fn probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    /// Update the probabilities with a (single swarm) particle swarm optimisation step, as done by
+    /// MOpt. Each probability is pulled towards the probability at which that mutator was most
+    /// efficient so far, and towards the share of the finds it was responsible for in this period.
+    /// `pulls` provides two uniformly random values in `[0, 1)` for each mutator.
+    fn adapt(&mut self, pulls: &[(f64, f64)]) {
+        let total_finds = self.period_finds.iter().sum::<u64>();
+        if total_finds > 0 {
+            for (i, &(local, global)) in pulls.iter().enumerate() {
+                if self.period_uses[i] > 0 {
+                    let efficiency = self.period_finds[i] as f64 / self.period_uses[i] as f64;
+                    if efficiency > self.best_efficiencies[i] {
+                        self.best_efficiencies[i] = efficiency;
+                        self.best_probabilities[i] = self.probabilities[i];
+                    }
+                }
+                let share = self.period_finds[i] as f64 / total_finds as f64;
+
+                self.velocities[i] = INERTIA * self.velocities[i]
+                    + local * (self.best_probabilities[i] - self.probabilities[i])
+                    + global * (share - self.probabilities[i]);
+                self.probabilities[i] += self.velocities[i];
+            }
+
+            // every mutator keeps the floor, and only the mass above it is renormalised, so that
+            // normalising cannot push a probability back below the floor
+            let count = self.probabilities.len() as f64;
+            let floor = MIN_PROBABILITY.min(1.0 / count);
+            let spare = 1.0 - floor * count;
+            self.probabilities
+                .iter_mut()
+                .for_each(|p| *p = (*p - floor).max(0.0));
+            let excess = self.probabilities.iter().sum::<f64>();
+            let share = |p: f64| {
+                if excess > 0.0 {
+                    p / excess
+                } else {
+                    1.0 / count
+                }
+            };
+            self.probabilities
+                .iter_mut()
+                .for_each(|p| *p = floor + spare * share(*p));
+        }
+
+        self.period_uses.fill(0);
+        self.period_finds.fill(0);
+    }
+}
+
+/// Mutator which picks one of several [`PGInput`] mutators per mutation, adapting the probability of
+/// picking each based on how often it produced a new corpus entry or solution, MOpt-style.
+///
+/// The statistics are kept in [`MutatorStatsMetadata`] on the state so that they survive restarts;
+/// use [`crate::feedbacks::MutatorStatsFeedback`] to report them. Only one of these mutators may be
+/// used per fuzzer, as they would otherwise share the metadata.
+pub struct PGAdaptiveMutator<MT> {
+    mutations: MT,
+    period: u64,
+    last: Option<usize>,
+    solutions_before: usize,
+}
+
+impl<MT> PGAdaptiveMutator<MT>
+where
+    MT: HasConstLen,
+{
+    /// Create a new adaptive mutator which picks from the provided mutators, updating the
+    /// probabilities every `period` executions.
+    pub fn new(mutations: MT, period: NonZeroU64) -> Self {
+        assert_ne!(MT::LEN, 0, "at least one mutator must be provided");
+        Self {
+            mutations,
+            period: period.get(),
+            last: None,
+            solutions_before: 0,
+        }
+    }
+}
+
+impl<MT> ComposedByMutations for PGAdaptiveMutator<MT> {
+    type Mutations = MT;
+
+    fn mutations(&self) -> &Self::Mutations {
+        &self.mutations
+    }
+
+    fn mutations_mut(&mut self) -> &mut Self::Mutations {
+        &mut self.mutations
+    }
+}
+
+impl<MT> Named for PGAdaptiveMutator<MT> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_adaptive");
+        &NAME
+    }
+}
+
+impl<MT, S> Mutator<PGInput, S> for PGAdaptiveMutator<MT>
+where
+    MT: MutatorsTuple<PGInput, S> + NamedTuple,
+    S: HasRand + HasMetadata + HasSolutions<PGInput>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PGInput) -> Result<MutationResult, Error> {
+        let pull = state.rand_mut().next_float();
+        let meta = state.metadata_or_insert_with(|| {
+            MutatorStatsMetadata::new(
+                self.mutations
+                    .names()
+                    .into_iter()
+                    .map(|name| name.to_string())
+                    .collect(),
+            )
+        });
+
+        // walk the cumulative distribution; fall back to the last mutator for rounding errors
+        let mut remaining = pull;
+        let idx = meta
+            .probabilities
+            .iter()
+            .position(|&p| {
+                remaining -= p;
+                remaining < 0.0
+            })
+            .unwrap_or(meta.probabilities.len() - 1);
+
+        self.last = Some(idx);
+        self.solutions_before = state.solutions().count();
+        self.mutations.get_and_mutate(idx.into(), state, input)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        let Some(idx) = self.last.take() else {
+            return Ok(());
+        };
+        self.mutations
+            .get_and_post_exec(idx, state, new_corpus_id)?;
+
+        let found = new_corpus_id.is_some() || state.solutions().count() > self.solutions_before;
+        let meta = state.metadata_mut::<MutatorStatsMetadata>()?;
+        meta.uses[idx] += 1;
+        meta.period_uses[idx] += 1;
+        if found {
+            meta.finds[idx] += 1;
+            meta.period_finds[idx] += 1;
+        }
+
+        if meta.period_uses.iter().sum::<u64>() >= self.period {
+            let pulls = (0..MT::LEN)
+                .map(|_| (state.rand_mut().next_float(), state.rand_mut().next_float()))
+                .collect::<Vec<_>>();
+            state.metadata_mut::<MutatorStatsMetadata>()?.adapt(&pulls);
+        }
+
+        Ok(())
+    }
+}
+
+/// The maximum number of distinct move sequences tracked by [`MacroDictionaryMetadata`] before the
+/// least recently seen ones are forgotten.
+const MACRO_CAPACITY: usize = 1 << 16;
+
+/// The number of distinct move sequences which [`MacroDictionaryMetadata`] keeps when it forgets
+/// sequences, so that it does not need to do so again for every new input.
+const MACRO_TARGET: usize = MACRO_CAPACITY / 4 * 3;
+
+/// A short sequence of moves which is learned and inserted as a unit by [`PGMacroMutator`].
+pub type Macro = Vec<(NonZeroUsize, Direction)>;
+
+/// How often and how recently a sequence in the [`MacroDictionaryMetadata`] was seen.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct MacroStats {
+    count: u64,
+    last: u64,
+}
+
+/// Metadata which holds the dictionary of move sequences learned by [`PGMacroMutator`], along with
+/// the number of interesting inputs each sequence was seen in.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct MacroDictionaryMetadata {
+    macros: HashMap<Macro, MacroStats>,
+    clock: u64,
+}
+
+impl_serdeany!(MacroDictionaryMetadata);
+
+impl MacroDictionaryMetadata {
+    /// An iterator over the move sequences which were seen in at least `min_count` inputs, along
+    /// with how many inputs they were seen in.
+    pub fn entries(&self, min_count: u64) -> impl Iterator<Item = (&Macro, u64)> {
+        self.macros
+            .iter()
+            .filter(move |(_, stats)| stats.count >= min_count)
+            .map(|(entry, stats)| (entry, stats.count))
+    }
+
+    /// The number of distinct move sequences in the dictionary.
+    pub fn len(&self) -> usize {
+        self.macros.len()
+    }
+
+    /// Whether the dictionary holds no move sequences at all.
+    pub fn is_empty(&self) -> bool {
+        self.macros.is_empty()
+    }
+
+    /// Count every distinct subsequence of the provided moves with a length between 2 and
+    /// `max_len` which ends after the first `known` moves, forgetting the least recently seen
+    /// sequences if the dictionary grows too large. The subsequences within the first `known` moves
+    /// (e.g. the moves shared with the input this one was mutated from) are expected to have been
+    /// learned already.
+    pub fn learn(&mut self, moves: &[(NonZeroUsize, Direction)], known: usize, max_len: usize) {
+        self.clock += 1;
+        let mut seen = HashSet::new();
+        for len in 2..=max_len {
+            for window in moves.windows(len).skip(known.saturating_sub(len - 1)) {
+                // only count each sequence once per input
+                if seen.insert(window) {
+                    self.count(window.to_vec(), 1);
+                }
+            }
+        }
+
+        self.shrink();
+    }
+
+    /// Seed the dictionary with a move sequence known to be useful (e.g. from a previous campaign on
+    /// the same puzzle), as if it had been seen in `count` more interesting inputs.
+    pub fn seed(&mut self, entry: Macro, count: u64) {
+        self.clock += 1;
+        self.count(entry, count);
+        self.shrink();
+    }
+
+    fn count(&mut self, entry: Macro, count: u64) {
+        let stats = self
+            .macros
+            .entry(entry)
+            .or_insert(MacroStats { count: 0, last: 0 });
+        stats.count += count;
+        stats.last = self.clock;
+    }
+
+    fn shrink(&mut self) {
+        if self.macros.len() > MACRO_CAPACITY {
+            // forget the least recently seen sequences, and the rarest among those first
+            let mut order = self
+                .macros
+                .iter()
+                .map(|(entry, stats)| (stats.last, stats.count, entry.clone()))
+                .collect::<Vec<_>>();
+            order.sort_unstable_by_key(|&(last, count, _)| (last, count));
+            for (_, _, entry) in order.into_iter().take(self.macros.len() - MACRO_TARGET) {
+                self.macros.remove(&entry);
+            }
+        }
+    }
+}
+
+/// Mutator which inserts a whole sequence of moves ("macro") which recurs across solutions and
+/// inputs that reached new states, e.g. shuffling a truck down and back up to let a car pass. The
+/// macro may be inserted at any point; the moves after it which are no longer valid are dropped, as
+/// in [`PGInsertMutator`].
+///
+/// The dictionary is stored in [`MacroDictionaryMetadata`] on the state. It is filled by
+/// [`crate::feedbacks::MacroFeedback`] from every new corpus entry and solution, and may be seeded
+/// up front with [`MacroDictionaryMetadata::seed`]. Since cars are identified by their index, the
+/// dictionary is specific to the puzzle being solved.
+pub struct PGMacroMutator<T> {
+    initial: State<T>,
+    min_count: u64,
+}
+
+impl<T> PGMacroMutator<T>
+where
+    T: Clone,
+{
+    /// Create a new mutator for the provided state, which inserts the sequences seen in at least
+    /// `min_count` interesting inputs.
+    pub fn new(state: &State<T>, min_count: u64) -> Self {
+        Self {
+            initial: state.clone(),
+            min_count,
+        }
+    }
+}
+
+impl<T> Named for PGMacroMutator<T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_macro");
+        &NAME
+    }
+}
+
+impl<S, T> Mutator<PGInput, S> for PGMacroMutator<T>
+where
+    S: HasRand + HasMetadata,
+    T: BoardValue,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut PGInput) -> Result<MutationResult, Error> {
+        let mut candidates = state
+            .metadata_or_insert_with(MacroDictionaryMetadata::default)
+            .entries(self.min_count)
+            .map(|(entry, _)| entry.clone())
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+
+        // any index can be selected, including the end
+        let at = state
+            .rand_mut()
+            .below(NonZeroUsize::new(input.moves().len() + 1).unwrap());
+
+        let mut prefix = self.initial.clone();
+        let mut board = prefix
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+        for &(car, dir) in &input.moves()[..at] {
+            if board.shift_car(car, dir).is_err() {
+                // the input was already broken by another mutator
+                return Ok(MutationResult::Skipped);
+            }
+        }
+        drop(board);
+
+        while !candidates.is_empty() {
+            let idx = state
+                .rand_mut()
+                .below(NonZeroUsize::new(candidates.len()).unwrap());
+            let candidate = candidates.swap_remove(idx);
+
+            let mut replay = prefix.clone();
+            let mut board = replay
+                .board_mut()
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+            if !candidate
+                .iter()
+                .all(|&(car, dir)| board.shift_car(car, dir).is_ok())
+            {
+                continue;
+            }
+
+            // repair: keep only the moves which are still valid after the insertion
+            let suffix = input.moves_mut().split_off(at);
+            input.moves_mut().extend(candidate);
+            for (car, dir) in suffix {
+                if board.shift_car(car, dir).is_ok() {
+                    input.moves_mut().push((car, dir));
+                }
+            }
+            return Ok(MutationResult::Mutated);
+        }
+
+        Ok(MutationResult::Skipped)
+    }
+
+    fn post_exec(&mut self, _state: &mut S, _new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::feedbacks::{
+        FinalStateMetadata, MacroFeedback, PrefixSnapshotFeedback, ViewMetadata,
+    };
+    use crate::input::PGInput;
+    use crate::mutators::{
+        MACRO_CAPACITY, MIN_PROBABILITY, MacroDictionaryMetadata, MutatorStatsMetadata,
+        PGAdaptiveMutator, PGBlockerMutator, PGInsertMutator, PGMacroMutator, PGTailMutator,
+        TailPolicy,
+    };
+    use crate::observers::{View, ViewFrom};
+    use libafl::HasMetadata;
+    use libafl::corpus::HasCurrentCorpusId;
+    use libafl::corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase};
+    use libafl::feedbacks::{Feedback, StateInitializer};
+    use libafl::mutators::{MutationResult, Mutator};
+    use libafl::state::{HasCorpus, StdState};
+    use libafl_bolts::rands::StdRand;
+    use libafl_bolts::tuples::tuple_list;
+    use parking_game::{Direction, State};
+    use std::error::Error;
+    use std::num::{NonZeroU64, NonZeroUsize};
+
+    type TestState = StdState<InMemoryCorpus<PGInput>, PGInput, StdRand, InMemoryCorpus<PGInput>>;
+
+    fn car(idx: usize) -> NonZeroUsize {
+        NonZeroUsize::new(idx).unwrap()
+    }
+
+    /// A board where the objective is blocked by 2, which is blocked by 3, while 4 is free to move
+    /// but irrelevant. The current testcase is the empty input, with the metadata needed by the
+    /// directed mutators written out by hand so this doesn't depend on step_until_seen.
+    fn blocked() -> Result<(State<u8>, TestState, CorpusId), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+            oo2.
+            ..2.
+            .33.
+            44..
+            "#,
+        );
+        let views: Vec<ViewFrom<u8>> = vec![
+            ViewFrom::new(
+                View::new(Direction::Left, None, 0),
+                View::new(Direction::Right, Some(car(2)), 0),
+            ),
+            ViewFrom::new(
+                View::new(Direction::Up, None, 0),
+                View::new(Direction::Down, Some(car(3)), 0),
+            ),
+            ViewFrom::new(
+                View::new(Direction::Left, None, 1),
+                View::new(Direction::Right, None, 1),
+            ),
+            ViewFrom::new(
+                View::new(Direction::Left, None, 0),
+                View::new(Direction::Right, None, 2),
+            ),
+        ];
+
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut testcase = Testcase::new(PGInput::new(vec![]));
+        testcase.add_metadata(ViewMetadata::new(views));
+        testcase.add_metadata(FinalStateMetadata::new(initial.clone()));
+        let id = state.corpus_mut().add(testcase)?;
+        state.set_corpus_id(id)?;
+
+        Ok((initial, state, id))
+    }
+
+    #[test]
+    fn tail_selection() -> Result<(), Box<dyn Error>> {
+        let (initial, mut state, _) = blocked()?;
+
+        let reversal = PGTailMutator::with_policy(&initial, TailPolicy::NoReversal);
+        let input = PGInput::new(vec![(car(3), Direction::Left)]);
+        let choices = vec![(car(3), Direction::Right, 2), (car(4), Direction::Right, 1)];
+        for _ in 0..64 {
+            let (selected, _, _) = reversal
+                .select(&mut state, &input, choices.clone())?
+                .unwrap();
+            assert_eq!(selected, car(4));
+        }
+        // ...unless there is no other way to go
+        let choices = vec![(car(3), Direction::Right, 1)];
+        assert!(reversal.select(&mut state, &input, choices)?.is_some());
+
+        let blockers = PGTailMutator::with_policy(&initial, TailPolicy::Blockers);
+        let choices = vec![(car(3), Direction::Left, 1), (car(4), Direction::Right, 1)];
+        let mut picked = 0;
+        for _ in 0..1000 {
+            let (selected, _, _) = blockers
+                .select(&mut state, &input, choices.clone())?
+                .unwrap();
+            picked += usize::from(selected == car(3));
+        }
+        // the blocker is four times as likely to be picked
+        assert!((700..900).contains(&picked), "picked {picked} times");
+
+        let uniform = PGTailMutator::with_policy(&initial, TailPolicy::Uniform);
+        assert!(uniform.select(&mut state, &input, vec![])?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn blocker_targeting() -> Result<(), Box<dyn Error>> {
+        let (initial, mut state, _) = blocked()?;
+        let mut mutator = PGBlockerMutator::new(&initial, 1.0);
+
+        for _ in 0..32 {
+            let mut input = PGInput::new(vec![]);
+            assert_eq!(
+                mutator.mutate(&mut state, &mut input)?,
+                MutationResult::Mutated
+            );
+            // neither the objective nor 2 can move, and 4 is not in the chain
+            assert_eq!(input.moves().len(), 1);
+            assert_eq!(input.moves()[0].0, car(3));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn insertion() -> Result<(), Box<dyn Error>> {
+        let (initial, mut state, id) = blocked()?;
+        let moves = vec![(car(3), Direction::Left), (car(2), Direction::Down)];
+        let input = PGInput::new(moves);
+        {
+            let mut testcase = state.corpus().get(id)?.borrow_mut();
+            *testcase.input_mut() = Some(input.clone());
+            PrefixSnapshotFeedback::new(&initial).append_metadata(
+                &mut (),
+                &mut (),
+                &(),
+                &mut testcase,
+            )?;
+        }
+
+        let mut mutator = PGInsertMutator::new(&initial);
+        let mut inserted = 0;
+        for _ in 0..32 {
+            let mut mutated = input.clone();
+            if mutator.mutate(&mut state, &mut mutated)? == MutationResult::Skipped {
+                continue;
+            }
+            inserted += 1;
+            // whatever was inserted, and wherever, the repaired input must still be valid
+            let mut replay = initial.clone();
+            let mut board = replay.board_mut()?;
+            for &(car, dir) in mutated.moves() {
+                board.shift_car(car, dir)?;
+            }
+        }
+        assert_ne!(inserted, 0);
+
+        // the snapshots don't describe a different input
+        let mut changed = PGInput::new(vec![(car(4), Direction::Right)]);
+        assert_eq!(
+            mutator.mutate(&mut state, &mut changed)?,
+            MutationResult::Skipped
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn adaptive_weighting() -> Result<(), Box<dyn Error>> {
+        let (initial, mut state, id) = blocked()?;
+        let mut mutator = PGAdaptiveMutator::new(
+            tuple_list!(
+                PGBlockerMutator::new(&initial, 1.0),
+                PGMacroMutator::<u8>::new(&initial, 1)
+            ),
+            NonZeroU64::new(16).unwrap(),
+        );
+
+        for _ in 0..512 {
+            let mut input = PGInput::new(vec![]);
+            mutator.mutate(&mut state, &mut input)?;
+            // only the blocker mutator ever finds anything
+            let found = (mutator.last == Some(0)).then_some(id);
+            mutator.post_exec(&mut state, found)?;
+        }
+
+        let meta = state.metadata::<MutatorStatsMetadata>()?;
+        let probabilities = meta.probabilities();
+        assert!(
+            probabilities[0] > probabilities[1],
+            "probabilities were {probabilities:?}"
+        );
+        // the starved mutator is kept at the floor, even after normalising
+        assert!(
+            probabilities.iter().all(|&p| p >= MIN_PROBABILITY),
+            "probabilities were {probabilities:?}"
+        );
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let (_, uses, finds) = meta.stats().nth(1).unwrap();
+        assert_ne!(uses, 0);
+        assert_eq!(finds, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn macro_dictionary() {
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let mut dictionary = MacroDictionaryMetadata::default();
+
+        // only the sequences ending after the known prefix are counted
+        let moves = [
+            (one, Direction::Left),
+            (two, Direction::Down),
+            (two, Direction::Down),
+            (one, Direction::Right),
+        ];
+        dictionary.learn(&moves, 3, 2);
+        let learned = dictionary
+            .entries(1)
+            .map(|(entry, _)| entry.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(learned, vec![moves[2..].to_vec()]);
+
+        // once full, the least recently seen sequences are forgotten, even if they were frequent
+        dictionary.seed(moves[..2].to_vec(), 16);
+        let cars = (1..=300).map(|car| NonZeroUsize::new(car).unwrap());
+        for (first, second) in cars.clone().flat_map(|a| cars.clone().map(move |b| (a, b))) {
+            dictionary.seed(vec![(first, Direction::Up), (second, Direction::Up)], 1);
+        }
+        assert!(dictionary.len() <= MACRO_CAPACITY);
+        assert!(dictionary.entries(2).next().is_none());
+        let newest = vec![(NonZeroUsize::new(300).unwrap(), Direction::Up); 2];
+        assert!(dictionary.entries(1).any(|(entry, _)| *entry == newest));
+    }
+
+    #[test]
+    fn learned_macro() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        ..2.
+        oo2.
+        ....
+        ....
+        "#,
+        );
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+
+        let mut learner = MacroFeedback::new(3);
+        learner.init_state(&mut state)?;
+        // only the truck shuffle is common to both
+        for moves in [
+            vec![
+                (two, Direction::Down),
+                (two, Direction::Down),
+                (one, Direction::Right),
+            ],
+            vec![
+                (one, Direction::Left),
+                (two, Direction::Down),
+                (two, Direction::Down),
+            ],
+        ] {
+            let mut testcase = Testcase::new(PGInput::new(moves));
+            learner.append_metadata(&mut state, &mut (), &(), &mut testcase)?;
+        }
+        let shuffle = vec![(two, Direction::Down); 2];
+        let learned = state
+            .metadata::<MacroDictionaryMetadata>()?
+            .entries(2)
+            .map(|(entry, _)| entry.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(learned, vec![shuffle.clone()]);
+
+        let mut mutator = PGMacroMutator::new(&initial, 2);
+        let mut input = PGInput::new(vec![]);
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input)?,
+            MutationResult::Mutated
+        );
+        assert_eq!(input.moves(), shuffle);
+
+        // wherever the macro is inserted, the repaired input remains valid
+        input.moves_mut().push((one, Direction::Right));
+        for _ in 0..16 {
+            let mut mutated = input.clone();
+            mutator.mutate(&mut state, &mut mutated)?;
+            assert!(mutated.moves().len() >= input.moves().len());
+            let mut replay = initial.clone();
+            let mut board = replay.board_mut()?;
+            for &(car, dir) in mutated.moves() {
+                board.shift_car(car, dir)?;
+            }
+        }
+
+        Ok(())
+    }
+}
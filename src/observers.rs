@@ -1,17 +1,58 @@
 //! Observers which collect data from [`crate::executor::PGExecutor`] executions.
 
+use crate::bitboard::{Bitboard, BitboardObserver};
 use crate::input::PGInput;
-use libafl::observers::{Observer, ObserverWithHashField};
-use libafl_bolts::{Error, Named};
-use parking_game::{Board, BoardValue, Direction, Orientation, Position, State};
+use libafl::observers::{
+    ExplicitTracking, MapObserver, Observer, ObserverWithHashField, StdMapObserver,
+};
+use libafl_bolts::rands::{Rand, StdRand};
+use libafl_bolts::{Error, HasLen, Named};
+use parking_game::{Board, BoardValue, Car, Dimensions, Direction, Orientation, Position, State};
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::hash::{DefaultHasher, Hash, Hasher};
 use std::num::NonZeroUsize;
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 
 /// An [`Observer`] compatible with [`crate::executor::PGExecutor`].
 pub trait PGObserver<T> {
+    /// Passes the board that the execution starts from to the observer, called after
+    /// [`Observer::pre_exec`] and before any moves are applied. `applied` is the number of moves of
+    /// the input which are already reflected in this board (e.g., when resuming from a snapshot).
+    /// Does nothing by default.
+    #[allow(unused_variables)]
+    fn initial_board(&mut self, applied: usize, board: &Board<impl Deref<Target = State<T>>, T>) {
+        // do nothing
+    }
+
+    /// Passes the board after each move is applied to the observer, along with the index of the
+    /// move in the input and the move itself. Does nothing by default.
+    #[allow(unused_variables)]
+    fn moved(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        // do nothing
+    }
+
+    /// Passes the first move which could not be applied to the observer, along with its index in
+    /// the input and the board it was attempted on. If this is called, the execution crashed, and
+    /// [`PGObserver::final_board`] is not called. Does nothing by default.
+    #[allow(unused_variables)]
+    fn illegal_move(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        // do nothing
+    }
+
     /// Passes the final board state to the observer, called after [`Observer::pre_exec`] and before
     /// [`Observer::post_exec`] if the execution completes normally. Does nothing by default so this
     /// may be implemented easily for existing observer types.
@@ -19,6 +60,26 @@ pub trait PGObserver<T> {
     fn final_board(&mut self, board: &Board<impl Deref<Target = State<T>>, T>) {
         // do nothing
     }
+
+    /// The views of each car on the final board, if this observer computed them in
+    /// [`PGObserver::final_board`] (i.e., it is a [`ViewObserver`]). Returns [`None`] by default.
+    fn final_views(&self) -> Option<&[ViewFrom<T>]> {
+        None
+    }
+
+    /// Passes the final board state to the observer along with the views of each car on it, as
+    /// computed by the [`ViewObserver`] in the same tuple, so that observers which derive their
+    /// measurements from the views need not compute them again. Called after
+    /// [`PGObserver::final_board`] of every observer in the tuple, and only if the tuple contains a
+    /// [`ViewObserver`]. Does nothing by default.
+    #[allow(unused_variables)]
+    fn final_board_views(
+        &mut self,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+        views: &[ViewFrom<T>],
+    ) {
+        // do nothing
+    }
 }
 
 /// Utility trait for marking [`libafl_bolts::tuples::tuple_list`]s as "all PG observers".
@@ -30,12 +91,101 @@ pub trait PGObserver<T> {
 /// every item in the list are upheld. This trait makes it possible to pass a board to all observers
 /// in a tuple list -- so long as all of `a`, `b`, and `c` all implement [`PGObserver`].
 pub trait PGObserverTuple<T> {
-    /// Iterate all boards contained here and pass the provided board.
-    fn final_board_all(&mut self, board: &Board<impl Deref<Target = State<T>>, T>);
+    /// Iterate all observers contained here and pass the provided starting board.
+    fn initial_board_all(
+        &mut self,
+        applied: usize,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    );
+
+    /// Iterate all observers contained here and pass the provided move and the board after it.
+    fn moved_all(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    );
+
+    /// Iterate all observers contained here and pass the provided illegal move and the board it
+    /// was attempted on.
+    fn illegal_move_all(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    );
+
+    /// Iterate all boards contained here and pass the provided board, then pass it again along with
+    /// the views of the [`ViewObserver`] contained here, if any.
+    fn final_board_all(&mut self, board: &Board<impl Deref<Target = State<T>>, T>)
+    where
+        T: Clone,
+    {
+        self.final_board_each(board);
+        if let Some(views) = self.final_views_any().map(<[_]>::to_vec) {
+            self.final_board_views_each(board, &views);
+        }
+    }
+
+    /// Iterate all observers contained here and pass the provided board.
+    fn final_board_each(&mut self, board: &Board<impl Deref<Target = State<T>>, T>);
+
+    /// The views of the first observer contained here which computed them, if any.
+    fn final_views_any(&self) -> Option<&[ViewFrom<T>]>;
+
+    /// Iterate all observers contained here and pass the provided board and its views.
+    fn final_board_views_each(
+        &mut self,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+        views: &[ViewFrom<T>],
+    );
 }
 
 impl<T> PGObserverTuple<T> for () {
-    fn final_board_all(&mut self, _board: &Board<impl Deref<Target = State<T>>, T>) {
+    fn initial_board_all(
+        &mut self,
+        _applied: usize,
+        _board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        // this is the end of the list, so we're done
+    }
+
+    fn moved_all(
+        &mut self,
+        _index: usize,
+        _car: NonZeroUsize,
+        _direction: Direction,
+        _board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        // this is the end of the list, so we're done
+    }
+
+    fn illegal_move_all(
+        &mut self,
+        _index: usize,
+        _car: NonZeroUsize,
+        _direction: Direction,
+        _board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        // this is the end of the list, so we're done
+    }
+
+    fn final_board_each(&mut self, _board: &Board<impl Deref<Target = State<T>>, T>) {
+        // this is the end of the list, so we're done
+    }
+
+    fn final_views_any(&self) -> Option<&[ViewFrom<T>]> {
+        // this is the end of the list, and nothing computed the views
+        None
+    }
+
+    fn final_board_views_each(
+        &mut self,
+        _board: &Board<impl Deref<Target = State<T>>, T>,
+        _views: &[ViewFrom<T>],
+    ) {
         // this is the end of the list, so we're done
     }
 }
@@ -47,9 +197,53 @@ where
     Head: PGObserver<T>,
     Tail: PGObserverTuple<T>,
 {
-    fn final_board_all(&mut self, board: &Board<impl Deref<Target = State<T>>, T>) {
+    fn initial_board_all(
+        &mut self,
+        applied: usize,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        self.0.initial_board(applied, board);
+        self.1.initial_board_all(applied, board);
+    }
+
+    fn moved_all(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        self.0.moved(index, car, direction, board);
+        self.1.moved_all(index, car, direction, board);
+    }
+
+    fn illegal_move_all(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        self.0.illegal_move(index, car, direction, board);
+        self.1.illegal_move_all(index, car, direction, board);
+    }
+
+    fn final_board_each(&mut self, board: &Board<impl Deref<Target = State<T>>, T>) {
         self.0.final_board(board);
-        self.1.final_board_all(board);
+        self.1.final_board_each(board);
+    }
+
+    fn final_views_any(&self) -> Option<&[ViewFrom<T>]> {
+        self.0.final_views().or_else(|| self.1.final_views_any())
+    }
+
+    fn final_board_views_each(
+        &mut self,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+        views: &[ViewFrom<T>],
+    ) {
+        self.0.final_board_views(board, views);
+        self.1.final_board_views_each(board, views);
     }
 }
 
@@ -57,6 +251,19 @@ where
 #[derive(Debug, Deserialize, Serialize)]
 pub struct FinalStateObserver<T> {
     final_state: Option<State<T>>,
+    canonical: Option<Reflection>,
+}
+
+/// The reflection of the board under which the states of a puzzle are identified by a
+/// [`FinalStateObserver::canonical`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+enum Reflection {
+    /// The board is not reflected, as no reflection keeps the objective car in its lane.
+    Identity,
+    /// The rows of the board are reversed, for an objective car in the middle row.
+    Rows,
+    /// The columns of the board are reversed, for an objective car in the middle column.
+    Columns,
 }
 
 impl<T> FinalStateObserver<T> {
@@ -66,9 +273,92 @@ impl<T> FinalStateObserver<T> {
     }
 }
 
+impl<T> FinalStateObserver<T>
+where
+    T: BoardValue,
+{
+    /// Create a [`FinalStateObserver`] which identifies the states of the provided puzzle up to
+    /// symmetry: cars other than the objective car with the same length and orientation are
+    /// interchangeable, and if the objective car is in the middle row (or column), states which
+    /// are mirror images of each other across it are the same. Both
+    /// [`ObserverWithHashField::hash`] and [`StateEncoding::encoding`] are then equal for such
+    /// states.
+    ///
+    /// A state and its mirror image are equally far from the solution, so they may be explored as
+    /// one. This only shrinks the states to explore on puzzles where both are reachable, e.g.
+    /// puzzles which are symmetric themselves.
+    pub fn canonical(initial: &State<T>) -> Self {
+        let (position, objective) = initial.cars()[0];
+        let dim = initial.dimensions();
+        let (row, column): (usize, usize) = ((*position.row()).into(), (*position.column()).into());
+        // reversing the rows (or columns) keeps the exit where it is, but the objective car only
+        // stays in its lane if that lane is in the middle
+        let reflection = match objective.orientation() {
+            Orientation::LeftRight if 2 * row + 1 == (*dim.rows()).into() => Reflection::Rows,
+            Orientation::UpDown if 2 * column + 1 == (*dim.columns()).into() => Reflection::Columns,
+            _ => Reflection::Identity,
+        };
+        Self {
+            final_state: None,
+            canonical: Some(reflection),
+        }
+    }
+
+    /// The cell index of each car's position in the final state, in car order. If this observer is
+    /// [`FinalStateObserver::canonical`], the cells of the least of the board and its mirror image
+    /// as unlabelled boards instead.
+    fn cells(&self) -> Option<Vec<usize>> {
+        let state = self.final_state.as_ref()?;
+        Some(match self.canonical {
+            None => state
+                .cars()
+                .iter()
+                .map(|(position, _)| position.as_index(state.dimensions()).unwrap())
+                .collect(),
+            Some(Reflection::Identity) => unlabelled_cells(state, Reflection::Identity),
+            Some(reflection) => unlabelled_cells(state, Reflection::Identity)
+                .min(unlabelled_cells(state, reflection)),
+        })
+    }
+}
+
+/// The cell index of each car's position in the provided state after the provided reflection,
+/// without regard for which of the cars of the same shape is where: the objective car first, then
+/// the other cars ordered by their orientation, length and cell. As the cars of each shape are the
+/// same in every state of a puzzle, this still identifies the states reachable from a given initial
+/// state.
+fn unlabelled_cells<T: BoardValue>(state: &State<T>, reflection: Reflection) -> Vec<usize> {
+    let dim = state.dimensions();
+    let (rows, columns): (usize, usize) = ((*dim.rows()).into(), (*dim.columns()).into());
+    let mut cars = state
+        .cars()
+        .iter()
+        .map(|(position, car)| {
+            let (mut row, mut column): (usize, usize) =
+                ((*position.row()).into(), (*position.column()).into());
+            let length: usize = (*car.length()).into();
+            let vertical = matches!(car.orientation(), Orientation::UpDown);
+            // the position is the top left cell of the car, which becomes another cell of it
+            match reflection {
+                Reflection::Identity => {}
+                Reflection::Rows => row = rows - row - if vertical { length } else { 1 },
+                Reflection::Columns => {
+                    column = columns - column - if vertical { 1 } else { length }
+                }
+            }
+            (vertical, length, row * columns + column)
+        })
+        .collect::<Vec<_>>();
+    cars[1..].sort_unstable();
+    cars.into_iter().map(|(_, _, cell)| cell).collect()
+}
+
 impl<T> Default for FinalStateObserver<T> {
     fn default() -> Self {
-        Self { final_state: None }
+        Self {
+            final_state: None,
+            canonical: None,
+        }
     }
 }
 
@@ -100,13 +390,27 @@ where
     }
 }
 
+impl<T> BitboardObserver<T> for FinalStateObserver<T>
+where
+    T: BoardValue,
+{
+    fn final_bitboard(&mut self, board: &Bitboard<T>) {
+        self.final_state = Some(board.to_state());
+    }
+}
+
 impl<T> ObserverWithHashField for FinalStateObserver<T>
 where
-    T: Hash,
+    T: BoardValue + Hash,
 {
     fn hash(&self) -> Option<u64> {
         if let Some(final_state) = &self.final_state {
             let mut hasher = DefaultHasher::new();
+            if self.canonical.is_some() {
+                // canonical mode: identical cars are unlabelled, and mirror images are the same
+                self.cells().hash(&mut hasher);
+                return Some(hasher.finish());
+            }
             // TODO(pt.0): build a hash which uniquely identifies the state
             //  - remember, not all parts of the state need to be hashed to identify it uniquely
             //  - only hash the parts which are necessary to distinguish the states
@@ -117,6 +421,469 @@ where
     }
 }
 
+/// Observers which can provide a compact encoding of the state they observed which is equal for two
+/// states if and only if the states are the same, e.g. for verifying that hashes did not collide.
+pub trait StateEncoding {
+    /// The encoding of the observed state, or [`None`] if there is no state (e.g., on a crash).
+    fn encoding(&self) -> Option<Vec<u8>>;
+}
+
+/// Encodes the provided state compactly: the index of each car's position, in car order, as
+/// [LEB128](https://en.wikipedia.org/wiki/LEB128). As the cars themselves never change, this
+/// uniquely identifies the states reachable from a given initial state.
+pub fn encode_state<T: BoardValue>(state: &State<T>) -> Vec<u8> {
+    encode_cells(
+        state
+            .cars()
+            .iter()
+            .map(|(position, _)| position.as_index(state.dimensions()).unwrap()),
+    )
+}
+
+fn encode_cells(cells: impl IntoIterator<Item = usize>) -> Vec<u8> {
+    let mut encoding = Vec::new();
+    for mut cell in cells {
+        while cell >= 0x80 {
+            encoding.push((cell as u8) | 0x80);
+            cell >>= 7;
+        }
+        encoding.push(cell as u8);
+    }
+    encoding
+}
+
+impl<T> StateEncoding for FinalStateObserver<T>
+where
+    T: BoardValue,
+{
+    fn encoding(&self) -> Option<Vec<u8>> {
+        self.cells().map(encode_cells)
+    }
+}
+
+/// Observer which records the hash of every intermediate board of an execution (and, if
+/// configured, the boards themselves), for cycle detection, minimisation, and visualisation.
+///
+/// When [`crate::executor::PGExecutor`] resumes from a snapshot, the trace starts at the snapshot;
+/// [`TraceObserver::applied`] tells how many moves of the input were skipped.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TraceObserver<T> {
+    applied: usize,
+    hashes: Vec<u64>,
+    states: Option<Vec<State<T>>>,
+}
+
+impl<T> TraceObserver<T> {
+    /// Create a new [`TraceObserver`], which also records every intermediate state if
+    /// `record_states` is set. Otherwise, only the hashes are recorded.
+    pub fn new(record_states: bool) -> Self {
+        Self {
+            applied: 0,
+            hashes: Vec::new(),
+            states: record_states.then(Vec::new),
+        }
+    }
+
+    /// The number of moves of the input which were already applied before the trace started.
+    pub fn applied(&self) -> usize {
+        self.applied
+    }
+
+    /// The hashes of each state in the trace, starting with the state that the execution started
+    /// from. The hash at index `i` is the state after move `applied + i` of the input.
+    pub fn hashes(&self) -> &[u64] {
+        &self.hashes
+    }
+
+    /// The states in the trace, if configured to record them. Indexed the same as
+    /// [`TraceObserver::hashes`].
+    pub fn states(&self) -> Option<&[State<T>]> {
+        self.states.as_deref()
+    }
+
+    /// The first cycle in the trace, if any: the number of moves of the input after which a state
+    /// was first reached, and the number of moves after which it was reached again. The moves in
+    /// between could be removed from the input without changing its outcome.
+    pub fn first_cycle(&self) -> Option<(usize, usize)> {
+        let mut seen = HashMap::new();
+        for (i, hash) in self.hashes.iter().enumerate() {
+            if let Some(first) = seen.insert(hash, i) {
+                return Some((self.applied + first, self.applied + i));
+            }
+        }
+        None
+    }
+
+    fn clear(&mut self) {
+        self.applied = 0;
+        self.hashes.clear();
+        if let Some(states) = &mut self.states {
+            states.clear();
+        }
+    }
+
+    fn record(&mut self, board: &Board<impl Deref<Target = State<T>>, T>)
+    where
+        T: Clone + Hash,
+    {
+        let mut hasher = DefaultHasher::new();
+        board.state().hash(&mut hasher);
+        self.hashes.push(hasher.finish());
+        if let Some(states) = &mut self.states {
+            states.push(board.state().clone());
+        }
+    }
+}
+
+impl<T> Named for TraceObserver<T> {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_trace");
+        &NAME
+    }
+}
+
+impl<S, T> Observer<PGInput, S> for TraceObserver<T> {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.clear();
+        Ok(())
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &PGInput) -> Result<(), Error> {
+        self.clear();
+        Ok(())
+    }
+}
+
+impl<T> PGObserver<T> for TraceObserver<T>
+where
+    T: Clone + Hash,
+{
+    fn initial_board(&mut self, applied: usize, board: &Board<impl Deref<Target = State<T>>, T>) {
+        self.applied = applied;
+        self.record(board);
+    }
+
+    fn moved(
+        &mut self,
+        _index: usize,
+        _car: NonZeroUsize,
+        _direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        self.record(board);
+    }
+}
+
+/// Why a move could not be applied. This mirrors [`parking_game::InvalidMoveType`], which is not
+/// accessible from [`parking_game::InvalidMoveError`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+pub enum IllegalMoveKind {
+    /// The car that was designated to be moved didn't exist.
+    InvalidCar,
+    /// The direction that was used isn't valid for the provided car.
+    InvalidDirection,
+    /// The final position of the car is out of bounds.
+    InvalidFinalPosition,
+    /// After moving the car, the car would intersect the provided car.
+    Intersects(NonZeroUsize),
+    /// The board rejected the move, but for none of the reasons above, i.e. the board disagrees
+    /// with [`IllegalMoveKind::classify`] about the rules.
+    Unknown,
+}
+
+impl IllegalMoveKind {
+    /// All the kinds of illegal moves, with an arbitrary car for [`IllegalMoveKind::Intersects`].
+    pub const ALL: [Self; 5] = [
+        Self::InvalidCar,
+        Self::InvalidDirection,
+        Self::InvalidFinalPosition,
+        Self::Intersects(NonZeroUsize::MIN),
+        Self::Unknown,
+    ];
+
+    /// Determines why moving the provided car in the provided direction on this board would fail,
+    /// or [`None`] if the move is legal.
+    pub fn classify<T: BoardValue>(
+        board: &Board<impl Deref<Target = State<T>>, T>,
+        car: NonZeroUsize,
+        direction: Direction,
+    ) -> Option<Self> {
+        let Some(&(position, actual)) = board.state().cars().get(car.get() - 1) else {
+            return Some(Self::InvalidCar);
+        };
+        let offset =
+            match (direction, actual.orientation()) {
+                (Direction::Up, Orientation::UpDown)
+                | (Direction::Left, Orientation::LeftRight) => T::one(),
+                (Direction::Down, Orientation::UpDown)
+                | (Direction::Right, Orientation::LeftRight) => *actual.length(),
+                _ => return Some(Self::InvalidDirection),
+            };
+        match position.shift(direction, offset).and_then(|p| board.get(p)) {
+            None => Some(Self::InvalidFinalPosition),
+            Some(Some(other)) => Some(Self::Intersects(other)),
+            Some(None) => None,
+        }
+    }
+
+    pub(crate) fn ordinal(&self) -> usize {
+        match self {
+            Self::InvalidCar => 0,
+            Self::InvalidDirection => 1,
+            Self::InvalidFinalPosition => 2,
+            Self::Intersects(_) => 3,
+            Self::Unknown => 4,
+        }
+    }
+
+    /// A short, stable name for this kind of illegal move (ignoring the car intersected with),
+    /// suitable for use in user stats.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::InvalidCar => "invalid_car",
+            Self::InvalidDirection => "invalid_direction",
+            Self::InvalidFinalPosition => "out_of_bounds",
+            Self::Intersects(_) => "intersects",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// The first move of an input which could not be applied.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct IllegalMove {
+    index: usize,
+    car: NonZeroUsize,
+    direction: Direction,
+    kind: IllegalMoveKind,
+}
+
+impl IllegalMove {
+    /// The index of the move in the input. The moves before it are valid, so truncating the input
+    /// to this length repairs it.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The car which was to be moved.
+    pub fn car(&self) -> NonZeroUsize {
+        self.car
+    }
+
+    /// The direction it was to be moved in.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Why the move could not be applied.
+    pub fn kind(&self) -> IllegalMoveKind {
+        self.kind
+    }
+}
+
+/// Observer which records the first illegal move of a crashing execution.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct IllegalMoveObserver {
+    illegal: Option<IllegalMove>,
+}
+
+impl IllegalMoveObserver {
+    /// The illegal move which crashed the execution, if it crashed.
+    pub fn illegal(&self) -> Option<&IllegalMove> {
+        self.illegal.as_ref()
+    }
+}
+
+impl Named for IllegalMoveObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_illegal_move");
+        &NAME
+    }
+}
+
+impl<S> Observer<PGInput, S> for IllegalMoveObserver {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.illegal = None;
+        Ok(())
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &PGInput) -> Result<(), Error> {
+        self.illegal = None;
+        Ok(())
+    }
+}
+
+impl<T> PGObserver<T> for IllegalMoveObserver
+where
+    T: BoardValue,
+{
+    fn illegal_move(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        // the executor only reports moves which failed, so if we think it's legal the board
+        // disagrees with us about the rules; record that rather than losing the move
+        let kind =
+            IllegalMoveKind::classify(board, car, direction).unwrap_or(IllegalMoveKind::Unknown);
+        self.illegal = Some(IllegalMove {
+            index,
+            car,
+            direction,
+            kind,
+        });
+    }
+}
+
+/// Random keys for [Zobrist hashing](https://en.wikipedia.org/wiki/Zobrist_hashing) of the states
+/// of one puzzle: one key per (car, position) pair. The hash of a state is the XOR of the keys of
+/// each car's position, so moving a car updates the hash with two XORs.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ZobristKeys {
+    keys: Vec<u64>,
+    cells: usize,
+}
+
+impl ZobristKeys {
+    /// Generate keys for the states reachable from the provided state. The same seed always
+    /// produces the same keys, so hashes are comparable between fuzzer instances.
+    pub fn new<T: BoardValue>(initial: &State<T>, seed: u64) -> Self {
+        let dim = initial.dimensions();
+        let cells = (*dim.rows()).into() * (*dim.columns()).into();
+        let mut rand = StdRand::with_seed(seed);
+        Self {
+            keys: (0..initial.cars().len() * cells)
+                .map(|_| rand.next())
+                .collect(),
+            cells,
+        }
+    }
+
+    /// The key of the provided car at the provided position.
+    pub fn key<T: BoardValue>(
+        &self,
+        car: NonZeroUsize,
+        position: Position<T>,
+        dim: &Dimensions<T>,
+    ) -> u64 {
+        self.keys[(car.get() - 1) * self.cells + position.as_index(dim).unwrap()]
+    }
+
+    /// The hash of the provided state, computed from scratch.
+    pub fn hash<T: BoardValue>(&self, state: &State<T>) -> u64 {
+        state
+            .cars()
+            .iter()
+            .enumerate()
+            .fold(0, |hash, (i, &(position, _))| {
+                hash ^ self.key(
+                    NonZeroUsize::new(i + 1).unwrap(),
+                    position,
+                    state.dimensions(),
+                )
+            })
+    }
+
+    /// The hash of the state after moving the provided car from `from` in the provided direction,
+    /// given the hash of the state before.
+    pub fn update<T: BoardValue>(
+        &self,
+        hash: u64,
+        car: NonZeroUsize,
+        from: Position<T>,
+        direction: Direction,
+        dim: &Dimensions<T>,
+    ) -> u64 {
+        let to = from.shift(direction, T::one()).unwrap();
+        hash ^ self.key(car, from, dim) ^ self.key(car, to, dim)
+    }
+}
+
+/// Observer which maintains the [`ZobristKeys`] hash of the board incrementally as each move is
+/// applied, rather than hashing the whole final state like [`FinalStateObserver`]. Only the board
+/// the execution starts from is hashed in full.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ZobristObserver {
+    keys: ZobristKeys,
+    current: Option<u64>,
+    completed: bool,
+}
+
+impl ZobristObserver {
+    /// Create a new [`ZobristObserver`] for the states reachable from the provided state.
+    pub fn new<T: BoardValue>(initial: &State<T>, seed: u64) -> Self {
+        Self {
+            keys: ZobristKeys::new(initial, seed),
+            current: None,
+            completed: false,
+        }
+    }
+
+    /// The keys used by this observer, e.g. for hashing snapshots the same way.
+    pub fn keys(&self) -> &ZobristKeys {
+        &self.keys
+    }
+}
+
+impl Named for ZobristObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_zobrist");
+        &NAME
+    }
+}
+
+impl<S> Observer<PGInput, S> for ZobristObserver {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.current = None;
+        self.completed = false;
+        Ok(())
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &PGInput) -> Result<(), Error> {
+        self.current = None;
+        self.completed = false;
+        Ok(())
+    }
+}
+
+impl<T> PGObserver<T> for ZobristObserver
+where
+    T: BoardValue,
+{
+    fn initial_board(&mut self, _applied: usize, board: &Board<impl Deref<Target = State<T>>, T>) {
+        self.current = Some(self.keys.hash(board.state()));
+    }
+
+    fn moved(
+        &mut self,
+        _index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        let state = board.state();
+        let to = state.cars()[car.get() - 1].0;
+        let from = to.shift(-direction, T::one()).unwrap();
+        self.current = self.current.map(|hash| {
+            self.keys
+                .update(hash, car, from, direction, state.dimensions())
+        });
+    }
+
+    fn final_board(&mut self, _board: &Board<impl Deref<Target = State<T>>, T>) {
+        self.completed = true;
+    }
+}
+
+impl ObserverWithHashField for ZobristObserver {
+    fn hash(&self) -> Option<u64> {
+        // like the final state observer, there is no hash if the execution did not complete
+        self.current.filter(|_| self.completed)
+    }
+}
+
 /// View from a car in a potential direction of travel. Useful for knowing where a car can move.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
 pub struct View<T> {
@@ -166,6 +933,11 @@ pub struct ViewFrom<T> {
 }
 
 impl<T> ViewFrom<T> {
+    /// Create a new [`ViewFrom`] with the provided views behind and ahead of the car.
+    pub fn new(backward: View<T>, forward: View<T>) -> Self {
+        Self { backward, forward }
+    }
+
     /// The view behind the car (if the car is oriented [`Orientation::LeftRight`], the view will
     /// have [`Direction::Left`]; otherwise, it will be [`Direction::Up`].
     pub fn backward(&self) -> &View<T> {
@@ -183,9 +955,24 @@ impl<T> ViewFrom<T> {
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct ViewObserver<T> {
     views: Vec<ViewFrom<T>>,
+    incremental: bool,
+    last: Option<(State<T>, Vec<ViewFrom<T>>)>,
 }
 
 impl<T> ViewObserver<T> {
+    /// Create a [`ViewObserver`] which keeps the last board it computed views for, and only
+    /// recomputes the views of the cars which share a row or column with a car that moved since.
+    /// This is much cheaper when consecutive executions end in similar boards (e.g., in snapshot
+    /// fuzzing or [`crate::stages::PGMutationStage`]), and no worse otherwise. Must only be used
+    /// with boards of a single puzzle.
+    pub fn incremental() -> Self {
+        Self {
+            views: Vec::new(),
+            incremental: true,
+            last: None,
+        }
+    }
+
     /// An iterator over the views. The objective car will be the first.
     pub fn views(&self) -> impl Iterator<Item = (NonZeroUsize, &ViewFrom<T>)> {
         self.views
@@ -237,36 +1024,602 @@ fn step_until_seen<T: BoardValue>(
     todo!("Implement as above!")
 }
 
-impl<T> PGObserver<T> for ViewObserver<T>
-where
-    T: BoardValue,
-{
-    fn final_board(&mut self, board: &Board<impl Deref<Target = State<T>>, T>) {
-        for (position, car) in board.state().cars().iter().copied() {
-            let backward = match car.orientation() {
-                Orientation::UpDown => Direction::Up,
-                Orientation::LeftRight => Direction::Left,
-            };
+/// Computes the [`ViewFrom`] for each car on the board, in car order. The objective car will be
+/// the first.
+pub(crate) fn views_of<T: BoardValue>(
+    board: &Board<impl Deref<Target = State<T>>, T>,
+) -> impl Iterator<Item = ViewFrom<T>> {
+    board
+        .state()
+        .cars()
+        .iter()
+        .map(|&(position, car)| view_from(board, position, car))
+}
+
+fn view_from<T: BoardValue>(
+    board: &Board<impl Deref<Target = State<T>>, T>,
+    position: Position<T>,
+    car: Car<T>,
+) -> ViewFrom<T> {
+    let backward = match car.orientation() {
+        Orientation::UpDown => Direction::Up,
+        Orientation::LeftRight => Direction::Left,
+    };
+
+    let forward = step_until_seen(board, position, -backward);
+    let backward = step_until_seen(board, position, backward);
+
+    ViewFrom { backward, forward }
+}
+
+/// Updates the views of the provided board, which were correct before the provided car was moved
+/// from `previous` to where it is now (along its lane). Only the views of cars whose lane crosses
+/// the cells the car occupied before or after are recomputed.
+pub(crate) fn update_views<T: BoardValue>(
+    board: &Board<impl Deref<Target = State<T>>, T>,
+    views: &mut [ViewFrom<T>],
+    moved: NonZeroUsize,
+    previous: Position<T>,
+) {
+    let cars = board.state().cars();
+    let (current, car) = cars[moved.get() - 1];
+    let span = |before: T, after: T| {
+        (
+            before.min(after),
+            before.max(after) + *car.length() - T::one(),
+        )
+    };
+    let (rows, columns) = match car.orientation() {
+        Orientation::UpDown => (
+            span(*previous.row(), *current.row()),
+            (*current.column(), *current.column()),
+        ),
+        Orientation::LeftRight => (
+            (*current.row(), *current.row()),
+            span(*previous.column(), *current.column()),
+        ),
+    };
+
+    for (view, &(position, other)) in views.iter_mut().zip(cars) {
+        let (lane, (first, last)) = match other.orientation() {
+            Orientation::UpDown => (*position.column(), columns),
+            Orientation::LeftRight => (*position.row(), rows),
+        };
+        if first <= lane && lane <= last {
+            *view = view_from(board, position, other);
+        }
+    }
+}
+
+/// Computes the chain of cars blocking the objective car from the exit, along with the depth at
+/// which each car was found in the chain.
+///
+/// Cars at depth 1 sit directly between the objective car and the wall ahead of it. A car at depth
+/// `n` which has room to clear the cells it is in the way of (backward or forward) ends the chain
+/// there. Otherwise, each car it sees on a side without enough room which is not already in the
+/// chain is at depth `n + 1`, and is in the way of the cells that the car at depth `n` must move
+/// through on that side. The objective car itself is never part of the chain. `views` must be the
+/// views of the provided board, in car order.
+pub fn blocker_chain<T: BoardValue>(
+    board: &Board<impl Deref<Target = State<T>>, T>,
+    views: &[ViewFrom<T>],
+) -> Vec<(NonZeroUsize, usize)> {
+    let cars = board.state().cars();
+    // whether the car moves vertically, the row or column it moves in, and the first and last
+    // cells it occupies along it
+    let span = |car: NonZeroUsize| {
+        let (position, car) = cars[car.get() - 1];
+        let (row, column, length) = (
+            (*position.row()).into(),
+            (*position.column()).into(),
+            (*car.length()).into(),
+        );
+        match car.orientation() {
+            Orientation::UpDown => (true, column, row, row + length - 1),
+            Orientation::LeftRight => (false, row, column, column + length - 1),
+        }
+    };
+
+    let (position, objective) = cars[0];
+    let forward = match objective.orientation() {
+        Orientation::UpDown => Direction::Down,
+        Orientation::LeftRight => Direction::Right,
+    };
+    let (vertical, lane, _, last) = span(NonZeroUsize::new(1).unwrap());
+    let end = match objective.orientation() {
+        Orientation::UpDown => (*board.state().dimensions().rows()).into(),
+        Orientation::LeftRight => (*board.state().dimensions().columns()).into(),
+    };
+
+    // each car in the chain, and the cells (as above) it must clear
+    let mut chain: Vec<(NonZeroUsize, usize)> = Vec::new();
+    let mut paths = Vec::new();
+    let mut offset = *objective.length();
+    while let Some(cell) = position.shift(forward, offset).and_then(|p| board.get(p)) {
+        if let Some(blocker) = cell
+            && chain.iter().all(|&(car, _)| car != blocker)
+        {
+            chain.push((blocker, 1));
+            paths.push((vertical, lane, last + 1, end - 1));
+        }
+        offset += T::one();
+    }
+
+    let mut next = 0;
+    while let Some(&(car, depth)) = chain.get(next) {
+        let (vertical, lane, first, last) = span(car);
+        let (path_vertical, path_lane, from, to) = paths[next];
+        // cars in the same row or column must clear the whole path, others only the cell they cross
+        let (from, to) = if vertical == path_vertical {
+            (from, to)
+        } else {
+            (path_lane, path_lane)
+        };
+        let view = &views[car.get() - 1];
+        let room: (usize, usize) = (
+            (*view.backward.distance()).into(),
+            (*view.forward.distance()).into(),
+        );
+        let needed = (
+            (last + 1).saturating_sub(from),
+            (to + 1).saturating_sub(first),
+        );
+        next += 1;
+        if room.0 >= needed.0 || room.1 >= needed.1 {
+            continue;
+        }
+
+        if let Some(seen) = view.backward.observed {
+            let cells = (first.saturating_sub(needed.0), first - room.0 - 1);
+            if seen.get() != 1 && chain.iter().all(|&(car, _)| car != seen) {
+                chain.push((seen, depth + 1));
+                paths.push((vertical, lane, cells.0, cells.1));
+            }
+        }
+        if let Some(seen) = view.forward.observed {
+            let cells = (last + room.1 + 1, last + needed.1);
+            if seen.get() != 1 && chain.iter().all(|&(car, _)| car != seen) {
+                chain.push((seen, depth + 1));
+                paths.push((vertical, lane, cells.0, cells.1));
+            }
+        }
+    }
+
+    chain
+}
+
+impl<T> PGObserver<T> for ViewObserver<T>
+where
+    T: BoardValue,
+{
+    fn final_board(&mut self, board: &Board<impl Deref<Target = State<T>>, T>) {
+        if !self.incremental {
+            self.views.extend(views_of(board));
+            return;
+        }
+
+        let state = board.state();
+        match &mut self.last {
+            Some((last, views)) if last.cars().len() == state.cars().len() => {
+                for (i, (&(before, _), &(after, _))) in
+                    last.cars().iter().zip(state.cars()).enumerate()
+                {
+                    if before.row() != after.row() || before.column() != after.column() {
+                        update_views(board, views, NonZeroUsize::new(i + 1).unwrap(), before);
+                    }
+                }
+                last.clone_from(state);
+            }
+            _ => self.last = Some((state.clone(), views_of(board).collect())),
+        }
+        self.views.clone_from(&self.last.as_ref().unwrap().1);
+    }
+
+    fn final_views(&self) -> Option<&[ViewFrom<T>]> {
+        Some(&self.views)
+    }
+}
+
+impl<T> BitboardObserver<T> for ViewObserver<T>
+where
+    T: BoardValue,
+{
+    fn final_bitboard(&mut self, board: &Bitboard<T>) {
+        self.views.extend(board.views());
+    }
+}
+
+/// Observer which measures how far the final board is from being solved: how many cars sit between
+/// the objective car and the exit, and how deep the chain of cars blocking those cars is (see
+/// [`blocker_chain`]).
+///
+/// The chain is derived from the views of the [`ViewObserver`] in the same tuple, which must be
+/// present; otherwise, nothing is measured.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BlockerObserver {
+    blockers: Option<usize>,
+    depth: Option<usize>,
+}
+
+impl BlockerObserver {
+    /// The number of cars directly between the objective car and the exit, if the execution
+    /// completed normally.
+    pub fn blockers(&self) -> Option<usize> {
+        self.blockers
+    }
+
+    /// The depth of the blocker chain, if the execution completed normally. This is zero if the
+    /// objective car can drive out and one if each of the cars in its way has room to move out of
+    /// it.
+    pub fn depth(&self) -> Option<usize> {
+        self.depth
+    }
+
+    /// The heuristic distance from the solution (the sum of [`BlockerObserver::blockers`] and
+    /// [`BlockerObserver::depth`]), if the execution completed normally. Lower is better; zero is
+    /// solved.
+    pub fn heuristic(&self) -> Option<usize> {
+        Some(self.blockers? + self.depth?)
+    }
+}
+
+impl Named for BlockerObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_blockers");
+        &NAME
+    }
+}
+
+impl<S> Observer<PGInput, S> for BlockerObserver {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.blockers = None;
+        self.depth = None;
+        Ok(())
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &PGInput) -> Result<(), Error> {
+        self.blockers = None;
+        self.depth = None;
+        Ok(())
+    }
+}
+
+impl<T> PGObserver<T> for BlockerObserver
+where
+    T: BoardValue,
+{
+    fn final_board_views(
+        &mut self,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+        views: &[ViewFrom<T>],
+    ) {
+        let chain = blocker_chain(board, views);
+        self.blockers = Some(chain.iter().filter(|&&(_, depth)| depth == 1).count());
+        self.depth = Some(
+            chain
+                .iter()
+                .map(|&(_, depth)| depth)
+                .max()
+                .unwrap_or_default(),
+        );
+    }
+}
+
+/// Observer which measures how "open" the final board is: how many moves could be made from it, and
+/// how many cars could make them, as derived from the [`View`]s of each car.
+///
+/// The views are those of the [`ViewObserver`] in the same tuple, which must be present; otherwise,
+/// nothing is measured.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct MobilityObserver {
+    moves: Option<usize>,
+    movable: Option<usize>,
+}
+
+impl MobilityObserver {
+    /// The number of legal (car, direction, distance) moves on the final board, if the execution
+    /// completed normally.
+    pub fn moves(&self) -> Option<usize> {
+        self.moves
+    }
+
+    /// The number of cars which could move at all on the final board, if the execution completed
+    /// normally.
+    pub fn movable(&self) -> Option<usize> {
+        self.movable
+    }
+
+    fn measure<T: BoardValue>(&mut self, views: impl Iterator<Item = ViewFrom<T>>) {
+        let (mut moves, mut movable) = (0, 0);
+        for view in views {
+            let distances: usize =
+                (*view.backward.distance()).into() + (*view.forward.distance()).into();
+            moves += distances;
+            movable += usize::from(distances > 0);
+        }
+        self.moves = Some(moves);
+        self.movable = Some(movable);
+    }
+}
+
+impl Named for MobilityObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("pg_mobility");
+        &NAME
+    }
+}
+
+impl<S> Observer<PGInput, S> for MobilityObserver {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.moves = None;
+        self.movable = None;
+        Ok(())
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &PGInput) -> Result<(), Error> {
+        self.moves = None;
+        self.movable = None;
+        Ok(())
+    }
+}
+
+impl<T> PGObserver<T> for MobilityObserver
+where
+    T: BoardValue,
+{
+    fn final_board_views(
+        &mut self,
+        _board: &Board<impl Deref<Target = State<T>>, T>,
+        views: &[ViewFrom<T>],
+    ) {
+        self.measure(views.iter().cloned());
+    }
+}
+
+impl<T> BitboardObserver<T> for MobilityObserver
+where
+    T: BoardValue,
+{
+    fn final_bitboard(&mut self, board: &Bitboard<T>) {
+        self.measure(board.views());
+    }
+}
+
+/// What each entry of a [`CoverageMapObserver`] represents.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum CoverageMode {
+    /// One entry per (car, position) pair, set when that car was at that position.
+    Positions,
+    /// One entry per (car, previous position, direction) triple, set when that car moved from the
+    /// previous position in that direction. As every move is by one unit, this is equivalent to
+    /// (car, position, previous position) but needs less space.
+    Transitions,
+}
+
+/// Observer which exposes the (car, position) pairs or transitions visited during an execution as
+/// an AFL-style coverage map, so that LibAFL's map-based components (e.g.,
+/// [`libafl::feedbacks::MaxMapFeedback`] or [`libafl::schedulers::MinimizerScheduler`]) may be used.
+///
+/// Unlike [`FinalStateObserver`], two boards which differ in only one car share most of their
+/// coverage. When [`crate::executor::PGExecutor`] resumes from a snapshot, only the positions from
+/// the snapshot onwards are covered.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CoverageMapObserver {
+    base: StdMapObserver<'static, u8, false>,
+    mode: CoverageMode,
+    cells: usize,
+}
+
+impl CoverageMapObserver {
+    /// Create a new [`CoverageMapObserver`] with a map large enough for the provided state.
+    pub fn new<T: BoardValue>(initial: &State<T>, mode: CoverageMode) -> Self {
+        let dim = initial.dimensions();
+        let cells = (*dim.rows()).into() * (*dim.columns()).into();
+        let size = match mode {
+            CoverageMode::Positions => initial.cars().len() * cells,
+            CoverageMode::Transitions => initial.cars().len() * cells * 2,
+        };
+        Self {
+            base: StdMapObserver::owned("pg_coverage", vec![0; size]),
+            mode,
+            cells,
+        }
+    }
+
+    /// What each entry of this map represents.
+    pub fn mode(&self) -> CoverageMode {
+        self.mode
+    }
+
+    fn cover<T: BoardValue>(
+        &mut self,
+        car: NonZeroUsize,
+        position: Position<T>,
+        dim: &Dimensions<T>,
+    ) {
+        let cell = position.as_index(dim).unwrap();
+        self.base[(car.get() - 1) * self.cells + cell] = 1;
+    }
+}
 
-            let forward = step_until_seen(board, position, -backward);
-            let backward = step_until_seen(board, position, backward);
+impl Named for CoverageMapObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        self.base.name()
+    }
+}
+
+impl<S> Observer<PGInput, S> for CoverageMapObserver {
+    fn flush(&mut self) -> Result<(), Error> {
+        self.base.reset_map()
+    }
+
+    fn pre_exec(&mut self, _state: &mut S, _input: &PGInput) -> Result<(), Error> {
+        self.base.reset_map()
+    }
+}
+
+impl<T> PGObserver<T> for CoverageMapObserver
+where
+    T: BoardValue,
+{
+    fn initial_board(&mut self, _applied: usize, board: &Board<impl Deref<Target = State<T>>, T>) {
+        if self.mode == CoverageMode::Positions {
+            let state = board.state();
+            for (i, &(position, _)) in state.cars().iter().enumerate() {
+                self.cover(
+                    NonZeroUsize::new(i + 1).unwrap(),
+                    position,
+                    state.dimensions(),
+                );
+            }
+        }
+    }
 
-            self.views.push(ViewFrom { backward, forward });
+    fn moved(
+        &mut self,
+        _index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        let state = board.state();
+        let position = state.cars()[car.get() - 1].0;
+        match self.mode {
+            CoverageMode::Positions => self.cover(car, position, state.dimensions()),
+            CoverageMode::Transitions => {
+                let previous = position.shift(-direction, T::one()).unwrap();
+                let cell = previous.as_index(state.dimensions()).unwrap();
+                let forward = matches!(direction, Direction::Down | Direction::Right);
+                self.base[((car.get() - 1) * self.cells + cell) * 2 + usize::from(forward)] = 1;
+            }
         }
     }
 }
 
+impl HasLen for CoverageMapObserver {
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+}
+
+impl AsRef<Self> for CoverageMapObserver {
+    fn as_ref(&self) -> &Self {
+        self
+    }
+}
+
+impl AsMut<Self> for CoverageMapObserver {
+    fn as_mut(&mut self) -> &mut Self {
+        self
+    }
+}
+
+// This gives us `AsSlice` and `AsIter`, which the map feedbacks need.
+impl Deref for CoverageMapObserver {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for CoverageMapObserver {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl MapObserver for CoverageMapObserver {
+    type Entry = u8;
+
+    fn get(&self, idx: usize) -> u8 {
+        self.base.get(idx)
+    }
+
+    fn set(&mut self, idx: usize, val: u8) {
+        self.base.set(idx, val);
+    }
+
+    fn usable_count(&self) -> usize {
+        self.base.usable_count()
+    }
+
+    fn count_bytes(&self) -> u64 {
+        self.base.count_bytes()
+    }
+
+    fn initial(&self) -> u8 {
+        self.base.initial()
+    }
+
+    fn reset_map(&mut self) -> Result<(), Error> {
+        self.base.reset_map()
+    }
+
+    fn to_vec(&self) -> Vec<u8> {
+        self.base.to_vec()
+    }
+
+    fn how_many_set(&self, indexes: &[usize]) -> usize {
+        self.base.how_many_set(indexes)
+    }
+}
+
+// Map observers are wrapped in this when index or novelty tracking is enabled (e.g., for
+// `MinimizerScheduler`), so we need to pass the boards through.
+impl<T, M, const ITH: bool, const NTH: bool> PGObserver<T> for ExplicitTracking<M, ITH, NTH>
+where
+    M: PGObserver<T>,
+{
+    fn initial_board(&mut self, applied: usize, board: &Board<impl Deref<Target = State<T>>, T>) {
+        self.as_mut().initial_board(applied, board);
+    }
+
+    fn moved(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        self.as_mut().moved(index, car, direction, board);
+    }
+
+    fn illegal_move(
+        &mut self,
+        index: usize,
+        car: NonZeroUsize,
+        direction: Direction,
+        board: &Board<impl Deref<Target = State<T>>, T>,
+    ) {
+        self.as_mut().illegal_move(index, car, direction, board);
+    }
+
+    fn final_board(&mut self, board: &Board<impl Deref<Target = State<T>>, T>) {
+        self.as_mut().final_board(board);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::input::PGInput;
-    use crate::observers::{FinalStateObserver, PGObserverTuple, View, ViewObserver};
+    use crate::observers::{
+        CoverageMapObserver, CoverageMode, FinalStateObserver, IllegalMoveKind,
+        IllegalMoveObserver, MobilityObserver, PGObserverTuple, StateEncoding, TraceObserver, View,
+        ViewFrom, ViewObserver, ZobristObserver, blocker_chain, encode_state, views_of,
+    };
+    use libafl::corpus::Testcase;
+    use libafl::events::SimpleEventManager;
     use libafl::executors::ExitKind;
-    use libafl::observers::{ObserverWithHashField, ObserversTuple};
+    use libafl::feedbacks::{Feedback, MaxMapFeedback, StateInitializer};
+    use libafl::observers::{MapObserver, ObserverWithHashField, ObserversTuple};
     use libafl::state::NopState;
+    use libafl_bolts::rands::{Rand, StdRand};
     use libafl_bolts::tuples::{Handled, tuple_list};
-    use parking_game::Direction;
+    use parking_game::{Direction, State};
     use std::error::Error;
     use std::num::NonZeroUsize;
+    use std::time::Instant;
 
     #[test]
     fn simple_observation() -> Result<(), Box<dyn Error>> {
@@ -419,4 +1772,530 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn blocker_depth() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+            oo2
+            ..2
+            .33
+            "#,
+        );
+        // written out by hand, so this doesn't depend on step_until_seen
+        let views = [
+            ViewFrom {
+                backward: View::new(Direction::Left, None, 0),
+                forward: View::new(Direction::Right, NonZeroUsize::new(2), 0),
+            },
+            ViewFrom {
+                backward: View::new(Direction::Up, None, 0),
+                forward: View::new(Direction::Down, NonZeroUsize::new(3), 0),
+            },
+            ViewFrom {
+                backward: View::new(Direction::Left, None, 1),
+                forward: View::new(Direction::Right, None, 0),
+            },
+        ];
+
+        let chain = blocker_chain(&initial.board()?, &views);
+        assert_eq!(
+            vec![
+                (NonZeroUsize::new(2).unwrap(), 1),
+                (NonZeroUsize::new(3).unwrap(), 2)
+            ],
+            chain
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn blocker_free_side() -> Result<(), Box<dyn Error>> {
+        let car = |idx| NonZeroUsize::new(idx);
+        // 2 sees 3 above it, but has room to move down out of the way
+        let initial = crate::parse_map::<u8>(
+            r#"
+            ..3.
+            ..3.
+            oo2.
+            ..2.
+            ....
+            "#,
+        );
+        let views = [
+            ViewFrom {
+                backward: View::new(Direction::Left, None, 0),
+                forward: View::new(Direction::Right, car(2), 0),
+            },
+            ViewFrom {
+                backward: View::new(Direction::Up, car(3), 0),
+                forward: View::new(Direction::Down, None, 1),
+            },
+            ViewFrom {
+                backward: View::new(Direction::Up, None, 0),
+                forward: View::new(Direction::Down, car(2), 0),
+            },
+        ];
+        assert_eq!(
+            vec![(car(2).unwrap(), 1)],
+            blocker_chain(&initial.board()?, &views)
+        );
+
+        // now 2 is stuck on both sides: 3 can't make room, but 4 can
+        let initial = crate::parse_map::<u8>(
+            r#"
+            ..3.
+            ..3.
+            oo2.
+            ..2.
+            ..44
+            "#,
+        );
+        let views = [
+            views[0],
+            ViewFrom {
+                backward: View::new(Direction::Up, car(3), 0),
+                forward: View::new(Direction::Down, car(4), 0),
+            },
+            views[2],
+            ViewFrom {
+                backward: View::new(Direction::Left, None, 2),
+                forward: View::new(Direction::Right, None, 0),
+            },
+        ];
+        assert_eq!(
+            vec![
+                (car(2).unwrap(), 1),
+                (car(3).unwrap(), 2),
+                (car(4).unwrap(), 2)
+            ],
+            blocker_chain(&initial.board()?, &views)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn trace_cycle() -> Result<(), Box<dyn Error>> {
+        let mut initial = crate::parse_map::<u8>("oo..");
+        let obs = TraceObserver::<u8>::new(true);
+
+        let mut observers = tuple_list!(obs);
+
+        let mut state = NopState::<PGInput>::new();
+
+        let car = NonZeroUsize::new(1).unwrap();
+        let input = PGInput::new(vec![
+            (car, Direction::Right),
+            (car, Direction::Right),
+            (car, Direction::Left),
+        ]);
+        observers.pre_exec_all(&mut state, &input)?;
+        let mut board = initial.board_mut()?;
+        observers.initial_board_all(0, &board);
+        for (index, &(car, direction)) in input.moves().iter().enumerate() {
+            board.shift_car(car, direction)?;
+            observers.moved_all(index, car, direction, &board);
+        }
+        observers.final_board_all(&board);
+        observers.post_exec_all(&mut state, &input, &ExitKind::Ok)?;
+
+        assert_eq!(4, observers.0.hashes().len());
+        assert_eq!(4, observers.0.states().unwrap().len());
+        assert_eq!(2, *observers.0.states().unwrap()[2].cars()[0].0.column());
+        // the state after the first move is reached again after the third
+        assert_eq!(Some((1, 3)), observers.0.first_cycle());
+
+        Ok(())
+    }
+
+    #[test]
+    fn coverage_map() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>("oo..\n22..");
+        let obs = CoverageMapObserver::new(&initial, CoverageMode::Positions);
+        let mut feedback = MaxMapFeedback::new(&obs);
+
+        let mut observers = tuple_list!(obs);
+
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+        feedback.init_state(&mut state)?;
+
+        let car = NonZeroUsize::new(1).unwrap();
+        for (moves, covered, interesting) in [
+            (vec![(car, Direction::Right)], 3, true),
+            // same positions, just visited more often
+            (
+                vec![
+                    (car, Direction::Right),
+                    (car, Direction::Left),
+                    (car, Direction::Right),
+                ],
+                3,
+                false,
+            ),
+            (
+                vec![(car, Direction::Right), (car, Direction::Right)],
+                4,
+                true,
+            ),
+        ] {
+            let input = PGInput::new(moves);
+            let mut replay = initial.clone();
+            let mut board = replay.board_mut()?;
+            observers.pre_exec_all(&mut state, &input)?;
+            observers.initial_board_all(0, &board);
+            for (index, &(car, direction)) in input.moves().iter().enumerate() {
+                board.shift_car(car, direction)?;
+                observers.moved_all(index, car, direction, &board);
+            }
+            observers.final_board_all(&board);
+            observers.post_exec_all(&mut state, &input, &ExitKind::Ok)?;
+
+            assert_eq!(covered, observers.0.count_bytes());
+            assert_eq!(
+                interesting,
+                feedback.is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)?
+            );
+            if interesting {
+                // this is where the map feedback updates its history
+                let mut testcase = Testcase::new(input);
+                feedback.append_metadata(&mut state, &mut mgr, &observers, &mut testcase)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn transition_coverage() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>("oo..\n22..");
+        let obs = CoverageMapObserver::new(&initial, CoverageMode::Transitions);
+        let mut feedback = MaxMapFeedback::new(&obs);
+
+        let mut observers = tuple_list!(obs);
+
+        let mut state = NopState::<PGInput>::new();
+        let mut mgr = SimpleEventManager::<PGInput, _, NopState<PGInput>>::printing();
+        feedback.init_state(&mut state)?;
+
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let mut covered = Vec::new();
+        for (moves, interesting) in [
+            (vec![(one, Direction::Right)], true),
+            // the way back is a different transition
+            (vec![(one, Direction::Right), (one, Direction::Left)], true),
+            // same transitions, just taken more often
+            (
+                vec![
+                    (one, Direction::Right),
+                    (one, Direction::Left),
+                    (one, Direction::Right),
+                ],
+                false,
+            ),
+            // the same transition of another car
+            (vec![(two, Direction::Right)], true),
+        ] {
+            let input = PGInput::new(moves);
+            let mut replay = initial.clone();
+            let mut board = replay.board_mut()?;
+            observers.pre_exec_all(&mut state, &input)?;
+            observers.initial_board_all(0, &board);
+            for (index, &(car, direction)) in input.moves().iter().enumerate() {
+                board.shift_car(car, direction)?;
+                observers.moved_all(index, car, direction, &board);
+            }
+            observers.final_board_all(&board);
+            observers.post_exec_all(&mut state, &input, &ExitKind::Ok)?;
+
+            covered.push(
+                (0..observers.0.usable_count())
+                    .filter(|&i| observers.0.get(i) != 0)
+                    .collect::<Vec<_>>(),
+            );
+            assert_eq!(
+                interesting,
+                feedback.is_interesting(&mut state, &mut mgr, &input, &observers, &ExitKind::Ok)?
+            );
+            if interesting {
+                // this is where the map feedback updates its history
+                let mut testcase = Testcase::new(input);
+                feedback.append_metadata(&mut state, &mut mgr, &observers, &mut testcase)?;
+            }
+        }
+
+        // each (car, from, to) transition has an entry of its own
+        let (right, back, other) = (&covered[0], &covered[1], &covered[3]);
+        assert_eq!(1, right.len());
+        assert_eq!(2, back.len());
+        assert!(back.contains(&right[0]));
+        assert_eq!(back, &covered[2]);
+        assert_eq!(1, other.len());
+        assert!(!back.contains(&other[0]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn illegal_move_kinds() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+            oo2
+            ..2
+            ...
+            "#,
+        );
+        let board = initial.board()?;
+        let (one, two, three) = (
+            NonZeroUsize::new(1).unwrap(),
+            NonZeroUsize::new(2).unwrap(),
+            NonZeroUsize::new(3).unwrap(),
+        );
+
+        for (car, direction, kind) in [
+            (
+                one,
+                Direction::Right,
+                Some(IllegalMoveKind::Intersects(two)),
+            ),
+            (
+                one,
+                Direction::Left,
+                Some(IllegalMoveKind::InvalidFinalPosition),
+            ),
+            (one, Direction::Up, Some(IllegalMoveKind::InvalidDirection)),
+            (
+                two,
+                Direction::Up,
+                Some(IllegalMoveKind::InvalidFinalPosition),
+            ),
+            (two, Direction::Down, None),
+            (three, Direction::Down, Some(IllegalMoveKind::InvalidCar)),
+        ] {
+            assert_eq!(kind, IllegalMoveKind::classify(&board, car, direction));
+            // the classification must agree with the board itself
+            let mut replay = initial.clone();
+            assert_eq!(
+                kind.is_none(),
+                replay.board_mut()?.shift_car(car, direction).is_ok()
+            );
+        }
+
+        let mut observers = tuple_list!(IllegalMoveObserver::default());
+        let mut state = NopState::<PGInput>::new();
+        let input = PGInput::new(vec![(two, Direction::Down), (one, Direction::Right)]);
+        observers.pre_exec_all(&mut state, &input)?;
+        observers.illegal_move_all(1, one, Direction::Right, &board);
+        observers.post_exec_all(&mut state, &input, &ExitKind::Crash)?;
+
+        let illegal = observers.0.illegal().unwrap();
+        assert_eq!(1, illegal.index());
+        assert_eq!(IllegalMoveKind::Intersects(two), illegal.kind());
+
+        observers.pre_exec_all(&mut state, &input)?;
+        assert!(observers.0.illegal().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn zobrist_incremental() -> Result<(), Box<dyn Error>> {
+        let mut initial = crate::parse_map::<u8>("oo..\n22..\n..3.\n..3.");
+        let obs = ZobristObserver::new(&initial, 0);
+
+        let mut observers = tuple_list!(obs);
+
+        let mut state = NopState::<PGInput>::new();
+
+        let (one, three) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(3).unwrap());
+        let input = PGInput::new(vec![
+            (one, Direction::Right),
+            (three, Direction::Up),
+            (one, Direction::Right),
+        ]);
+        let start = observers.0.keys().hash(&initial);
+        observers.pre_exec_all(&mut state, &input)?;
+        let mut board = initial.board_mut()?;
+        observers.initial_board_all(0, &board);
+        for (index, &(car, direction)) in input.moves().iter().enumerate() {
+            board.shift_car(car, direction)?;
+            observers.moved_all(index, car, direction, &board);
+        }
+        observers.final_board_all(&board);
+        observers.post_exec_all(&mut state, &input, &ExitKind::Ok)?;
+
+        let hash = observers.0.hash().unwrap();
+        assert_eq!(observers.0.keys().hash(&initial), hash);
+        assert_ne!(start, hash);
+
+        // no hash for a crash, even though the moves before it were observed
+        observers.pre_exec_all(&mut state, &input)?;
+        observers.initial_board_all(0, &initial.board()?);
+        observers.post_exec_all(&mut state, &input, &ExitKind::Crash)?;
+        assert!(observers.0.hash().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_states() -> Result<(), Box<dyn Error>> {
+        // the objective car is in the middle row, so boards are mirrored across it
+        let initial = crate::parse_map::<u8>("22....\noo..4.\n33..4.");
+        let mut observers = tuple_list!(FinalStateObserver::canonical(&initial));
+
+        let mut state = NopState::<PGInput>::new();
+
+        let nop_input = PGInput::new(vec![]);
+        let mut observe = |moves: &[(usize, Direction)]| -> Result<_, Box<dyn Error>> {
+            let mut replay = initial.clone();
+            let mut board = replay.board_mut()?;
+            for &(car, direction) in moves {
+                board.shift_car(NonZeroUsize::new(car).unwrap(), direction)?;
+            }
+            observers.pre_exec_all(&mut state, &nop_input)?;
+            observers.final_board_all(&board);
+            observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+            Ok((
+                observers.0.hash().unwrap(),
+                observers.0.encoding().unwrap(),
+                encode_state(board.state()),
+            ))
+        };
+
+        // mirror images of each other, with 2 and 3 swapping places
+        let two = observe(&[(2, Direction::Right), (4, Direction::Up)])?;
+        let three = observe(&[(3, Direction::Right)])?;
+        assert_ne!(two.2, three.2);
+        assert_eq!((two.0, &two.1), (three.0, &three.1));
+
+        // not mirror images of each other, as 4 is in the same place
+        let four = observe(&[(2, Direction::Right)])?;
+        assert_ne!(four.0, three.0);
+        assert_ne!(four.1, three.1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn mobility() -> Result<(), Box<dyn Error>> {
+        // the views of this board, written out by hand so this doesn't depend on step_until_seen:
+        //   oo..2
+        //   ....2
+        //   33...
+        let views = [
+            ViewFrom {
+                backward: View::new(Direction::Left, None, 0),
+                forward: View::new(Direction::Right, NonZeroUsize::new(2), 2),
+            },
+            ViewFrom {
+                backward: View::new(Direction::Up, None, 0),
+                forward: View::new(Direction::Down, None, 1),
+            },
+            ViewFrom {
+                backward: View::new(Direction::Left, None, 0),
+                forward: View::new(Direction::Right, None, 3u8),
+            },
+        ];
+        let initial = crate::parse_map::<u8>("oo..2\n....2\n33...");
+        let mut observers = tuple_list!(MobilityObserver::default());
+
+        let mut state = NopState::<PGInput>::new();
+
+        // without a ViewObserver in the tuple, there are no views to measure
+        let nop_input = PGInput::new(vec![]);
+        observers.pre_exec_all(&mut state, &nop_input)?;
+        observers.final_board_all(&initial.board()?);
+        observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+        assert_eq!(None, observers.0.moves());
+
+        // which the ViewObserver would otherwise pass along like this
+        observers.pre_exec_all(&mut state, &nop_input)?;
+        observers.final_board_views_each(&initial.board()?, &views);
+        observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+
+        // o: 2 right; 2: 1 down; 3: 3 right
+        assert_eq!(Some(6), observers.0.moves());
+        assert_eq!(Some(3), observers.0.movable());
+
+        Ok(())
+    }
+
+    /// Random walk of valid moves on the provided map, returning every board along the way.
+    fn random_walk(map: &str, steps: usize) -> Vec<State<u8>> {
+        let mut current = crate::parse_map::<u8>(map);
+        let cars = current.cars().len();
+        let mut rand = StdRand::with_seed(0);
+        let mut walk = vec![current.clone()];
+        while walk.len() <= steps {
+            let car = NonZeroUsize::new(rand.between(1, cars)).unwrap();
+            let direction = *rand
+                .choose(&[
+                    Direction::Up,
+                    Direction::Down,
+                    Direction::Left,
+                    Direction::Right,
+                ])
+                .unwrap();
+            if current
+                .board_mut()
+                .unwrap()
+                .shift_car(car, direction)
+                .is_ok()
+            {
+                walk.push(current.clone());
+            }
+        }
+        walk
+    }
+
+    #[test]
+    fn incremental_views() -> Result<(), Box<dyn Error>> {
+        let mut observers = tuple_list!(ViewObserver::<u8>::incremental());
+
+        let mut state = NopState::<PGInput>::new();
+
+        let nop_input = PGInput::new(vec![]);
+        for current in random_walk(include_str!("../maps/tokyo36.map"), 1000) {
+            observers.pre_exec_all(&mut state, &nop_input)?;
+            observers.final_board_all(&current.board()?);
+            observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+
+            let expected = views_of(&current.board()?).collect::<Vec<_>>();
+            for ((_, actual), expected) in observers.0.views().zip(&expected) {
+                assert_eq!(expected.backward, actual.backward);
+                assert_eq!(expected.forward, actual.forward);
+            }
+            assert_eq!(expected.len(), observers.0.views().count());
+        }
+
+        Ok(())
+    }
+
+    /// Compares the full and incremental view computation. Run with:
+    /// `cargo test --release -- --ignored --nocapture bench_views`
+    #[test]
+    #[ignore]
+    fn bench_views() -> Result<(), Box<dyn Error>> {
+        let walk = random_walk(include_str!("../maps/tokyo36.map"), 100_000);
+        let mut state = NopState::<PGInput>::new();
+        let nop_input = PGInput::new(vec![]);
+
+        for (name, obs) in [
+            ("full", ViewObserver::<u8>::default()),
+            ("incremental", ViewObserver::<u8>::incremental()),
+        ] {
+            let mut observers = tuple_list!(obs);
+            let start = Instant::now();
+            for current in &walk {
+                observers.pre_exec_all(&mut state, &nop_input)?;
+                observers.final_board_all(&current.board()?);
+                observers.post_exec_all(&mut state, &nop_input, &ExitKind::Ok)?;
+            }
+            println!("{name}: {:?} for {} boards", start.elapsed(), walk.len());
+        }
+
+        Ok(())
+    }
 }
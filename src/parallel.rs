@@ -0,0 +1,108 @@
+//! Multi-process fuzzing of [`parking_game`] puzzles on the local machine.
+//!
+//! [`launch`] starts a broker and one client process per core through LibAFL's [`Launcher`]. Each
+//! client builds its own fuzzer and communicates through a [`PGManager`]: new corpus entries and
+//! solutions found by one client are sent to the others (which evaluate them against their own
+//! feedbacks), and the user stats fired by feedbacks (e.g., the crash rate) are aggregated over all
+//! clients by the broker's monitor according to their [`libafl::monitors::stats::AggregatorOps`].
+//!
+//! Clients may be restarted, in which case their state is serialized and handed to the new
+//! process, so every metadata type stored in the state or in its testcases must be serializable and
+//! registered with [`libafl_bolts::impl_serdeany`].
+
+use crate::input::PGInput;
+use libafl::events::{ClientDescription, EventConfig, Launcher, LlmpRestartingEventManager};
+use libafl::monitors::MultiMonitor;
+use libafl_bolts::Error;
+use libafl_bolts::core_affinity::Cores;
+use libafl_bolts::shmem::{ShMemProvider, StdShMemProvider};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// The event manager used by each client started by [`launch`].
+pub type PGManager<S> = LlmpRestartingEventManager<
+    (),
+    PGInput,
+    S,
+    <StdShMemProvider as ShMemProvider>::ShMem,
+    StdShMemProvider,
+>;
+
+/// Starts a broker on `broker_port` and a client on each of the provided cores, each of which runs
+/// `run_client`. `run_client` receives the state of the client if it is being restarted, the event
+/// manager to fuzz with, and a description of the client (e.g., to seed its random source
+/// differently from the others).
+///
+/// Each client process returns from this once `run_client` does. The broker runs in the calling
+/// process until it is interrupted (e.g., with Ctrl-C), so solutions should be reported by the
+/// clients.
+pub fn launch<CF, S>(cores: &Cores, broker_port: u16, run_client: CF) -> Result<(), Error>
+where
+    CF: FnOnce(Option<S>, PGManager<S>, ClientDescription) -> Result<(), Error>,
+    S: DeserializeOwned + Serialize,
+{
+    let monitor = MultiMonitor::new(|s| println!("{s}"));
+    let result = Launcher::builder()
+        .shmem_provider(StdShMemProvider::new()?)
+        .configuration(EventConfig::from_name("parking-game"))
+        .monitor(monitor)
+        .run_client(run_client)
+        .cores(cores)
+        .broker_port(broker_port)
+        .build()
+        .launch::<PGInput, S>();
+    match result {
+        // the clients exit once they are done fuzzing, which is not a failure
+        Err(Error::ShuttingDown) => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::feedbacks::BlockerMetadata;
+    use crate::input::PGInput;
+    use crate::schedulers::{NoveltyScheduler, novelty_score};
+    use crate::stages::ExhaustedMetadata;
+    use libafl::HasMetadata;
+    use libafl::corpus::{Corpus, InMemoryCorpus, Testcase};
+    use libafl::schedulers::Scheduler;
+    use libafl::state::{HasCorpus, StdState};
+    use libafl_bolts::rands::StdRand;
+    use parking_game::Direction;
+    use std::error::Error;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn state_round_trip() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>("oo...\n22...");
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::<PGInput>::new(),
+            &mut (),
+            &mut (),
+        )?;
+
+        let input = PGInput::new(vec![(NonZeroUsize::new(2).unwrap(), Direction::Right)]);
+        let mut testcase = Testcase::new(input.clone());
+        testcase.add_metadata(BlockerMetadata::new(1, 1));
+        testcase.add_metadata(ExhaustedMetadata::new(4));
+        let id = state.corpus_mut().add(testcase)?;
+        // adds metadata to both the testcase and the state
+        NoveltyScheduler::new(&initial).on_add(&mut state, id)?;
+        let score = novelty_score(&state, &state.corpus().get(id)?.borrow())?;
+
+        // this is what happens to the state when a client is restarted
+        let state: StdState<InMemoryCorpus<PGInput>, PGInput, StdRand, InMemoryCorpus<PGInput>> =
+            postcard::from_bytes(&postcard::to_allocvec(&state)?)?;
+
+        let testcase = state.corpus().get(id)?.borrow();
+        assert_eq!(input.moves(), testcase.input().as_ref().unwrap().moves());
+        assert_eq!(score, novelty_score(&state, &testcase)?);
+        assert_eq!(2, testcase.metadata::<BlockerMetadata>()?.heuristic());
+        assert_eq!(4, testcase.metadata::<ExhaustedMetadata>()?.successors());
+
+        Ok(())
+    }
+}
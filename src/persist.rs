@@ -0,0 +1,258 @@
+//! Persistent campaigns for [`parking_game`] puzzles, which can be resumed after the fuzzer stops.
+//!
+//! [`on_disk_corpora`] stores every corpus entry and solution in a directory as it is found: each
+//! input is written with [`libafl::inputs::Input::to_file`], and its metadata (e.g.,
+//! [`crate::feedbacks::ViewMetadata`] and [`crate::feedbacks::FinalStateMetadata`]) as JSON in a
+//! hidden `.<name>.metadata` file next to it. [`resume`] loads the corpus and solutions of such a
+//! directory into a new state.
+
+use crate::input::PGInput;
+use libafl::corpus::ondisk::OnDiskMetadataFormat;
+use libafl::corpus::{InMemoryOnDiskCorpus, OnDiskCorpus};
+use libafl::inputs::Input;
+use libafl::{Evaluator, EvaluatorObservers};
+use libafl_bolts::Error;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// The directory within a campaign directory which holds the corpus.
+pub const CORPUS_DIR: &str = "corpus";
+
+/// The directory within a campaign directory which holds the solutions.
+pub const SOLUTIONS_DIR: &str = "solutions";
+
+/// Creates the corpus and solutions of a campaign stored in `dir`, creating the directories as
+/// necessary. The corpus is also kept in memory, as it is used constantly.
+///
+/// Inputs are named by their hash, so adding an input which is already stored (e.g., when resuming)
+/// overwrites it. Only one fuzzer may use a campaign directory at a time; when fuzzing with
+/// [`crate::parallel::launch`], use one directory per client.
+pub fn on_disk_corpora(
+    dir: &Path,
+) -> Result<(InMemoryOnDiskCorpus<PGInput>, OnDiskCorpus<PGInput>), Error> {
+    let format = Some(OnDiskMetadataFormat::JsonPretty);
+    Ok((
+        InMemoryOnDiskCorpus::with_meta_format_and_prefix(
+            dir.join(CORPUS_DIR),
+            format.clone(),
+            None,
+            false,
+        )?,
+        OnDiskCorpus::with_meta_format_and_prefix(&dir.join(SOLUTIONS_DIR), format, None, false)?,
+    ))
+}
+
+/// Reads the inputs of the corpus of the campaign stored in `dir`, shortest first. Returns no
+/// inputs if there is no such corpus.
+pub fn corpus_inputs(dir: &Path) -> Result<Vec<PGInput>, Error> {
+    stored_inputs(&dir.join(CORPUS_DIR))
+}
+
+/// Reads the inputs of the solutions of the campaign stored in `dir`, shortest first. Returns no
+/// inputs if there are no such solutions.
+pub fn solution_inputs(dir: &Path) -> Result<Vec<PGInput>, Error> {
+    stored_inputs(&dir.join(SOLUTIONS_DIR))
+}
+
+fn stored_inputs(dir: &Path) -> Result<Vec<PGInput>, Error> {
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        // skip metadata and any other hidden files
+        if path.is_file()
+            && !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        {
+            inputs.push(PGInput::from_file(&path)?);
+        }
+    }
+    inputs.sort_by(|a, b| {
+        a.moves()
+            .len()
+            .cmp(&b.moves().len())
+            .then(a.moves().cmp(b.moves()))
+    });
+    Ok(inputs)
+}
+
+/// Resumes the campaign stored in `dir` by executing every input of its corpus and adding it to the
+/// corpus of the provided state, which should use the [`on_disk_corpora`] of the same directory,
+/// then executing every stored solution. Returns the number of corpus entries and solutions loaded.
+///
+/// The metadata of each entry is computed again by the feedbacks, rather than loaded from disk.
+/// This also rebuilds the state of the feedbacks themselves, e.g. the set of hashes which were
+/// already seen by a [`libafl::feedbacks::NewHashFeedback`] or a
+/// [`crate::feedbacks::StateNoveltyFeedback`], so the fuzzer continues where it stopped. Every
+/// input of the corpus is kept, even if the feedbacks no longer consider it interesting.
+///
+/// The solutions are evaluated without firing any events, so they are not reported as new again,
+/// and are only kept if the objective still considers them solutions. Corpus entries which are
+/// also solutions are evaluated in the same way (rather than always kept), as adding them to the
+/// corpus would report them again.
+///
+/// As every input is added to the corpora again, each of their files (and metadata files) on disk
+/// is rewritten; resuming a large campaign rewrites the whole directory.
+pub fn resume<E, EM, S, Z>(
+    fuzzer: &mut Z,
+    executor: &mut E,
+    state: &mut S,
+    manager: &mut EM,
+    dir: &Path,
+) -> Result<(usize, usize), Error>
+where
+    Z: Evaluator<E, EM, PGInput, S> + EvaluatorObservers<E, EM, PGInput, S>,
+{
+    let solutions = solution_inputs(dir)?;
+    let solved = solutions
+        .iter()
+        .map(|input| input.moves())
+        .collect::<HashSet<_>>();
+
+    let mut loaded = 0;
+    for input in corpus_inputs(dir)? {
+        // replayed with the solutions instead
+        if !solved.contains(input.moves()) {
+            fuzzer.add_input(state, executor, manager, input)?;
+            loaded += 1;
+        }
+    }
+
+    let mut kept = 0;
+    for input in &solutions {
+        let (result, id) =
+            fuzzer.evaluate_input_with_observers(state, executor, manager, input, false)?;
+        loaded += usize::from(id.is_some());
+        kept += usize::from(result.is_solution());
+    }
+    Ok((loaded, kept))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::PGExecutor;
+    use crate::feedbacks::{BlockerMetadata, SolvedFeedback};
+    use crate::input::PGInput;
+    use crate::observers::{FinalStateObserver, ViewObserver};
+    use crate::persist::{CORPUS_DIR, corpus_inputs, on_disk_corpora, resume};
+    use libafl::corpus::{Corpus, Testcase};
+    use libafl::events::SimpleEventManager;
+    use libafl::feedbacks::NewHashFeedback;
+    use libafl::inputs::Input;
+    use libafl::schedulers::QueueScheduler;
+    use libafl::state::{HasCorpus, HasSolutions, StdState};
+    use libafl::{Evaluator, HasMetadata, StdFuzzer};
+    use libafl_bolts::rands::StdRand;
+    use libafl_bolts::tuples::tuple_list;
+    use parking_game::Direction;
+    use std::error::Error;
+    use std::fs;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn campaign_on_disk() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("pg_campaign_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        assert!(corpus_inputs(&dir)?.is_empty());
+
+        let car = NonZeroUsize::new(1).unwrap();
+        let inputs = [
+            PGInput::new(vec![(car, Direction::Right), (car, Direction::Right)]),
+            PGInput::new(vec![]),
+            PGInput::new(vec![(car, Direction::Right)]),
+        ];
+
+        let (mut corpus, _) = on_disk_corpora(&dir)?;
+        for input in &inputs {
+            let mut testcase = Testcase::new(input.clone());
+            testcase.add_metadata(BlockerMetadata::new(input.moves().len(), 0));
+            corpus.add(testcase)?;
+        }
+        // adding the same input again overwrites it
+        corpus.add(Testcase::new(inputs[1].clone()))?;
+
+        let loaded = corpus_inputs(&dir)?;
+        assert_eq!(3, loaded.len());
+        for (expected, loaded) in [&inputs[1], &inputs[2], &inputs[0]]
+            .into_iter()
+            .zip(&loaded)
+        {
+            assert_eq!(expected.moves(), loaded.moves());
+        }
+
+        let metadata = fs::read_dir(dir.join(CORPUS_DIR))?
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .filter(|name| name.ends_with(".metadata"))
+            .collect::<Vec<_>>();
+        assert_eq!(3, metadata.len());
+        let name = format!(".{}.metadata", inputs[2].generate_name(None));
+        let stored = fs::read_to_string(dir.join(CORPUS_DIR).join(name))?;
+        assert!(stored.contains("\"blockers\": 1"));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn resume_campaign() -> Result<(), Box<dyn Error>> {
+        let dir = std::env::temp_dir().join(format!("pg_resume_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo.2
+        ...2
+        ....
+        "#,
+        );
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+
+        // a fresh campaign for each run, but on the same directory
+        let campaign = || -> Result<_, Box<dyn Error>> {
+            let views = ViewObserver::<u8>::default();
+            let final_state = FinalStateObserver::<u8>::default();
+            let mut feedback = NewHashFeedback::new(&final_state);
+            let mut objective = SolvedFeedback::new(&views);
+            let (corpus, solutions) = on_disk_corpora(&dir)?;
+            let state = StdState::new(
+                StdRand::with_seed(0),
+                corpus,
+                solutions,
+                &mut feedback,
+                &mut objective,
+            )?;
+            let fuzzer = StdFuzzer::new(QueueScheduler::new(), feedback, objective);
+            let executor = PGExecutor::new(initial.clone(), tuple_list!(views, final_state));
+            Ok((state, fuzzer, executor, SimpleEventManager::printing()))
+        };
+
+        let (mut state, mut fuzzer, mut executor, mut mgr) = campaign()?;
+        fuzzer.add_input(&mut state, &mut executor, &mut mgr, PGInput::new(vec![]))?;
+        for moves in [vec![(one, Direction::Right)], vec![(two, Direction::Down)]] {
+            fuzzer.evaluate_input(&mut state, &mut executor, &mut mgr, &PGInput::new(moves))?;
+        }
+        assert_eq!((3, 1), (state.corpus().count(), state.solutions().count()));
+        drop((state, fuzzer, executor));
+
+        let (mut state, mut fuzzer, mut executor, mut mgr) = campaign()?;
+        let loaded = resume(&mut fuzzer, &mut executor, &mut state, &mut mgr, &dir)?;
+        assert_eq!((3, 1), loaded);
+        assert_eq!((3, 1), (state.corpus().count(), state.solutions().count()));
+
+        // the feedback remembers the states which were already found
+        let (result, _) = fuzzer.evaluate_input(
+            &mut state,
+            &mut executor,
+            &mut mgr,
+            &PGInput::new(vec![(one, Direction::Right)]),
+        )?;
+        assert!(!result.is_corpus());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
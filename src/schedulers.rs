@@ -0,0 +1,1169 @@
+//! Corpus schedulers which make use of the structure of [`parking_game`] puzzles.
+
+use crate::feedbacks::{BlockerMetadata, MobilityMetadata, ViewMetadata};
+use crate::input::PGInput;
+use crate::stages::ExhaustedMetadata;
+use libafl::corpus::{Corpus, CorpusId, EnableDisableCorpus, Testcase};
+use libafl::schedulers::{HasQueueCycles, RemovableScheduler, Scheduler, TestcaseScore};
+use libafl::state::{HasCorpus, HasRand};
+use libafl::{HasMetadata, random_corpus_id};
+use libafl_bolts::rands::Rand;
+use libafl_bolts::tuples::MatchName;
+use libafl_bolts::{Error, impl_serdeany};
+use parking_game::{BoardValue, Direction, State};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+
+/// How the depth of a corpus entry is measured by the [`DepthScheduler`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum DepthMeasure {
+    /// The number of moves in the input; see [`PGInput::moves`].
+    #[default]
+    Moves,
+    /// The number of slides in the input; see [`PGInput::slides`].
+    Slides,
+}
+
+impl DepthMeasure {
+    /// The depth of the provided input according to this measure.
+    pub fn depth(&self, input: &PGInput) -> usize {
+        match self {
+            Self::Moves => input.moves().len(),
+            Self::Slides => input.slides(),
+        }
+    }
+}
+
+/// The entries of the corpus, and which of them have not been served yet in this cycle, ordered by
+/// priority (lowest first, then oldest first).
+#[derive(Debug, Clone)]
+struct OpenList<K> {
+    // the priority of each entry, and whether it was not served yet in this cycle
+    priorities: HashMap<CorpusId, (K, bool)>,
+    open: BinaryHeap<Reverse<(K, CorpusId)>>,
+    unserved: usize,
+    cycles: u64,
+}
+
+impl<K> Default for OpenList<K>
+where
+    K: Ord,
+{
+    fn default() -> Self {
+        Self {
+            priorities: HashMap::new(),
+            open: BinaryHeap::new(),
+            unserved: 0,
+            cycles: 0,
+        }
+    }
+}
+
+impl<K> OpenList<K>
+where
+    K: Copy + Ord,
+{
+    fn insert(&mut self, id: CorpusId, priority: K) {
+        if !self
+            .priorities
+            .insert(id, (priority, true))
+            .is_some_and(|(_, open)| open)
+        {
+            self.unserved += 1;
+        }
+        self.open.push(Reverse((priority, id)));
+    }
+
+    fn update(&mut self, id: CorpusId, priority: K) {
+        let Some((current, open)) = self.priorities.get_mut(&id) else {
+            return self.insert(id, priority);
+        };
+        // entries which were served already this cycle are queued with the new priority in the next
+        if *open && *current != priority {
+            self.open.push(Reverse((priority, id)));
+        }
+        *current = priority;
+    }
+
+    fn remove(&mut self, id: CorpusId) {
+        // the entry is dropped from the heap lazily when it comes up
+        if self.priorities.remove(&id).is_some_and(|(_, open)| open) {
+            self.unserved -= 1;
+        }
+    }
+
+    fn next(&mut self) -> Option<CorpusId> {
+        if self.priorities.is_empty() {
+            return None;
+        }
+        loop {
+            let Some(Reverse((priority, id))) = self.open.pop() else {
+                // everything has been served: start the next cycle
+                self.cycles += 1;
+                self.open
+                    .extend(self.priorities.iter_mut().map(|(&id, (priority, open))| {
+                        *open = true;
+                        Reverse((*priority, id))
+                    }));
+                self.unserved = self.priorities.len();
+                continue;
+            };
+            // skip entries which were removed, replaced or served since they were queued
+            if let Some((current, open)) = self.priorities.get_mut(&id)
+                && *current == priority
+                && *open
+            {
+                *open = false;
+                self.unserved -= 1;
+                return Some(id);
+            }
+        }
+    }
+}
+
+/// How a [`PriorityScheduler`] orders the corpus entries: the entry with the lowest priority is
+/// served first. This is implemented for functions of the input and testcase of an entry.
+pub trait EntryPriority {
+    /// The priority of a corpus entry.
+    type Key: Copy + Ord + Debug;
+
+    /// The priority of the corpus entry with the provided input and testcase.
+    fn priority(&self, input: &PGInput, testcase: &Testcase<PGInput>) -> Result<Self::Key, Error>;
+}
+
+impl<F, K> EntryPriority for F
+where
+    F: Fn(&PGInput, &Testcase<PGInput>) -> Result<K, Error>,
+    K: Copy + Ord + Debug,
+{
+    type Key = K;
+
+    fn priority(&self, input: &PGInput, testcase: &Testcase<PGInput>) -> Result<K, Error> {
+        self(input, testcase)
+    }
+}
+
+/// Scheduler which serves corpus entries in order of their priority, lowest first (then oldest
+/// first). Each entry is served once per cycle. Entries added during a cycle join it immediately,
+/// so a new entry with a low priority is served before the ones with higher priorities left.
+#[derive(Debug, Clone, Default)]
+pub struct PriorityScheduler<P>
+where
+    P: EntryPriority,
+{
+    priority: P,
+    list: OpenList<P::Key>,
+}
+
+impl<P> PriorityScheduler<P>
+where
+    P: EntryPriority,
+{
+    /// Create a new [`PriorityScheduler`] which orders the corpus entries by the provided
+    /// priority.
+    pub fn with_priority(priority: P) -> Self {
+        Self {
+            priority,
+            list: OpenList::default(),
+        }
+    }
+
+    /// How this scheduler orders the corpus entries.
+    pub fn priority(&self) -> &P {
+        &self.priority
+    }
+
+    /// The number of entries which have not yet been served in this cycle.
+    pub fn unexpanded(&self) -> usize {
+        self.list.unserved
+    }
+
+    fn entry_priority<S>(&self, state: &mut S, id: CorpusId) -> Result<P::Key, Error>
+    where
+        S: HasCorpus<PGInput>,
+    {
+        let mut testcase = state.corpus().get(id)?.borrow_mut();
+        testcase.load_input(state.corpus())?;
+        let input = testcase.input().as_ref().unwrap();
+        self.priority.priority(input, &testcase)
+    }
+}
+
+impl<P, S> RemovableScheduler<PGInput, S> for PriorityScheduler<P>
+where
+    P: EntryPriority,
+    S: HasCorpus<PGInput>,
+{
+    fn on_remove(
+        &mut self,
+        _state: &mut S,
+        id: CorpusId,
+        _testcase: &Option<Testcase<PGInput>>,
+    ) -> Result<(), Error> {
+        self.list.remove(id);
+        Ok(())
+    }
+
+    fn on_replace(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        prev: &Testcase<PGInput>,
+    ) -> Result<(), Error> {
+        // the replacement keeps its parent and its place in the cycle
+        let mut testcase = state.corpus().get(id)?.borrow_mut();
+        if testcase.parent_id().is_none() {
+            testcase.set_parent_id_optional(prev.parent_id());
+        }
+        drop(testcase);
+        let priority = self.entry_priority(state, id)?;
+        self.list.update(id, priority);
+        Ok(())
+    }
+}
+
+impl<P, S> Scheduler<PGInput, S> for PriorityScheduler<P>
+where
+    P: EntryPriority,
+    S: HasCorpus<PGInput>,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        let current_id = *state.corpus().current();
+        state
+            .corpus()
+            .get(id)?
+            .borrow_mut()
+            .set_parent_id_optional(current_id);
+        let priority = self.entry_priority(state, id)?;
+        self.list.insert(id, priority);
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        let id = self
+            .list
+            .next()
+            .ok_or_else(|| Error::empty("No entries in corpus.".to_string()))?;
+        <Self as Scheduler<PGInput, S>>::set_current_scheduled(self, state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+impl<P> HasQueueCycles for PriorityScheduler<P>
+where
+    P: EntryPriority,
+{
+    fn queue_cycles(&self) -> u64 {
+        self.list.cycles
+    }
+}
+
+impl EntryPriority for DepthMeasure {
+    type Key = usize;
+
+    fn priority(&self, input: &PGInput, _testcase: &Testcase<PGInput>) -> Result<usize, Error> {
+        Ok(self.depth(input))
+    }
+}
+
+/// Scheduler which serves corpus entries in order of their depth, shallowest first, like a
+/// breadth-first search. Each entry is served once per cycle. Entries added during a cycle join it
+/// immediately, so shallow entries that were just found are served before the deeper ones left.
+///
+/// When every entry is fully expanded when it is served (e.g., with
+/// [`crate::stages::PGMutationStage`]), the first solution found has the lowest depth possible.
+pub type DepthScheduler = PriorityScheduler<DepthMeasure>;
+
+impl DepthScheduler {
+    /// Create a new [`DepthScheduler`] which measures depth with the provided measure.
+    pub fn new(measure: DepthMeasure) -> Self {
+        Self::with_priority(measure)
+    }
+
+    /// How the depth of a corpus entry is measured by this scheduler.
+    pub fn measure(&self) -> DepthMeasure {
+        self.priority
+    }
+}
+
+/// A cost which is ordered by [`f64::total_cmp`].
+#[derive(Debug, Copy, Clone)]
+pub struct Cost(pub f64);
+
+impl PartialEq for Cost {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Cost {}
+
+impl PartialOrd for Cost {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Cost {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// The priority of the [`BestFirstScheduler`]: the path cost so far (the depth) plus a weighted
+/// estimate of the remaining cost (the [`BlockerMetadata::heuristic`]).
+#[derive(Debug, Copy, Clone)]
+pub struct BestFirstPriority {
+    measure: DepthMeasure,
+    weight: f64,
+}
+
+impl EntryPriority for BestFirstPriority {
+    type Key = Cost;
+
+    fn priority(&self, input: &PGInput, testcase: &Testcase<PGInput>) -> Result<Cost, Error> {
+        let heuristic = testcase.metadata::<BlockerMetadata>()?.heuristic();
+        let cost = self.measure.depth(input);
+        Ok(Cost(cost as f64 + self.weight * heuristic as f64))
+    }
+}
+
+/// Scheduler which serves corpus entries in order of their path cost so far (their depth) plus a
+/// weighted estimate of the remaining cost (the [`BlockerMetadata::heuristic`]), lowest first, like
+/// A* search with the corpus as the open list. Like [`DepthScheduler`], each entry is served once
+/// per cycle.
+///
+/// Every corpus entry must have [`BlockerMetadata`], i.e. [`crate::feedbacks::BlockerFeedback`]
+/// must be part of the feedback.
+pub type BestFirstScheduler = PriorityScheduler<BestFirstPriority>;
+
+impl BestFirstScheduler {
+    /// Create a new [`BestFirstScheduler`] which measures the cost so far with the provided measure
+    /// and multiplies the heuristic by the provided weight. A weight of 1 is plain A*; higher
+    /// weights search more greedily, and a weight of 0 is the same as [`DepthScheduler`].
+    pub fn new(measure: DepthMeasure, weight: f64) -> Self {
+        Self::with_priority(BestFirstPriority { measure, weight })
+    }
+
+    /// How the cost so far of a corpus entry is measured by this scheduler.
+    pub fn measure(&self) -> DepthMeasure {
+        self.priority.measure
+    }
+
+    /// The weight of the heuristic relative to the cost so far.
+    pub fn weight(&self) -> f64 {
+        self.priority.weight
+    }
+}
+
+/// Scheduler wrapper which skips testcases marked with [`ExhaustedMetadata`], as there is nothing
+/// left to find by mutating them again. Exhausted testcases are optionally also disabled in the
+/// corpus, so that other components (e.g., splicing mutators) don't pick them either.
+///
+/// Besides stages which add [`ExhaustedMetadata`] themselves, this marks the current testcase as
+/// exhausted once every one of its one-move successors (according to its
+/// [`crate::feedbacks::ViewMetadata`], i.e. [`crate::feedbacks::ViewFeedback`] must be part of the
+/// feedback) has been evaluated by the fuzzer. Progress towards this is tracked in the
+/// [`ExpansionMetadata`] of the testcase.
+///
+/// The exhausted testcases and the number of remaining (frontier) testcases are tracked in
+/// [`RetirementMetadata`], so they survive restarts with the rest of the state, and may be reported
+/// with [`crate::feedbacks::RetirementStatsFeedback`]. If every testcase is exhausted, the search
+/// space has been fully explored and [`Scheduler::next`] fails.
+#[derive(Debug, Clone)]
+pub struct RetiringScheduler<CS, T> {
+    base: CS,
+    disable: bool,
+    phantom: PhantomData<T>,
+}
+
+impl<CS, T> RetiringScheduler<CS, T> {
+    /// Wrap the provided scheduler for the provided initial state, disabling exhausted testcases in
+    /// the corpus if `disable` is set.
+    pub fn new(_initial: &State<T>, base: CS, disable: bool) -> Self {
+        Self {
+            base,
+            disable,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The wrapped scheduler.
+    pub fn base(&self) -> &CS {
+        &self.base
+    }
+
+    fn frontier<S>(&self, state: &S) -> usize
+    where
+        S: HasCorpus<PGInput> + HasMetadata,
+    {
+        if self.disable {
+            // the retired testcases are no longer counted
+            state.corpus().count()
+        } else {
+            let retired = state
+                .metadata::<RetirementMetadata>()
+                .map_or(0, |meta| meta.retired.len());
+            state.corpus().count() - retired
+        }
+    }
+}
+
+impl<CS, T> RetiringScheduler<CS, T>
+where
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    /// Records the input as evaluated if it is a one-move successor of the current testcase, and
+    /// marks the testcase as exhausted once all of them were.
+    fn expand<S>(&self, state: &mut S, input: &PGInput) -> Result<(), Error>
+    where
+        S: HasCorpus<PGInput>,
+    {
+        let Some(id) = *state.corpus().current() else {
+            return Ok(());
+        };
+        let Some((&step, prefix)) = input.moves().split_last() else {
+            return Ok(());
+        };
+        let mut testcase = state.corpus().get(id)?.borrow_mut();
+        if testcase.has_metadata::<ExhaustedMetadata>()
+            || testcase.load_input(state.corpus())?.moves() != prefix
+        {
+            return Ok(());
+        }
+        let Ok(views) = testcase.metadata::<ViewMetadata<T>>() else {
+            return Ok(());
+        };
+        let successors = views
+            .views()
+            .flat_map(|(car, view)| {
+                [view.backward(), view.forward()]
+                    .into_iter()
+                    .filter(|view| *view.distance() != T::zero())
+                    .map(move |view| (car, view.direction()))
+            })
+            .collect::<HashSet<_>>();
+        // illegal moves are not successors
+        if !successors.contains(&step) {
+            return Ok(());
+        }
+
+        let expansion = testcase.metadata_or_insert_with(ExpansionMetadata::default);
+        expansion.evaluated.insert(step);
+        if expansion.evaluated.len() == successors.len() {
+            testcase.remove_metadata::<ExpansionMetadata>();
+            testcase.add_metadata(ExhaustedMetadata::new(successors.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Metadata which tracks the one-move successors of a testcase which have been evaluated while it
+/// was the current testcase, for the [`RetiringScheduler`]. Removed once the testcase is exhausted.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ExpansionMetadata {
+    evaluated: HashSet<(NonZeroUsize, Direction)>,
+}
+
+impl ExpansionMetadata {
+    /// The number of one-move successors of the testcase which have been evaluated.
+    pub fn evaluated(&self) -> usize {
+        self.evaluated.len()
+    }
+}
+
+impl_serdeany!(ExpansionMetadata);
+
+/// Metadata which tracks which testcases were retired by the [`RetiringScheduler`].
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct RetirementMetadata {
+    retired: HashSet<CorpusId>,
+    frontier: usize,
+}
+
+impl RetirementMetadata {
+    /// The number of testcases which have been retired because they were exhausted.
+    pub fn exhausted(&self) -> usize {
+        self.retired.len()
+    }
+
+    /// The number of testcases which have not been retired.
+    pub fn frontier(&self) -> usize {
+        self.frontier
+    }
+}
+
+impl_serdeany!(RetirementMetadata);
+
+impl<CS, S, T> RemovableScheduler<PGInput, S> for RetiringScheduler<CS, T>
+where
+    CS: RemovableScheduler<PGInput, S>,
+    S: HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        testcase: &Option<Testcase<PGInput>>,
+    ) -> Result<(), Error> {
+        if let Ok(meta) = state.metadata_mut::<RetirementMetadata>() {
+            meta.retired.remove(&id);
+        }
+        self.base.on_remove(state, id, testcase)
+    }
+
+    fn on_replace(
+        &mut self,
+        state: &mut S,
+        id: CorpusId,
+        prev: &Testcase<PGInput>,
+    ) -> Result<(), Error> {
+        if let Ok(meta) = state.metadata_mut::<RetirementMetadata>() {
+            meta.retired.remove(&id);
+        }
+        self.base.on_replace(state, id, prev)
+    }
+}
+
+impl<CS, S, T> Scheduler<PGInput, S> for RetiringScheduler<CS, T>
+where
+    CS: Scheduler<PGInput, S> + RemovableScheduler<PGInput, S>,
+    S: HasCorpus<PGInput> + HasMetadata,
+    S::Corpus: EnableDisableCorpus,
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        self.base.on_add(state, id)
+    }
+
+    fn on_evaluation<OT>(
+        &mut self,
+        state: &mut S,
+        input: &PGInput,
+        observers: &OT,
+    ) -> Result<(), Error>
+    where
+        OT: MatchName,
+    {
+        self.expand(state, input)?;
+        self.base.on_evaluation(state, input, observers)
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        loop {
+            if self.frontier(state) == 0 {
+                return Err(Error::empty(
+                    "Every corpus entry is exhausted; the search space is fully explored."
+                        .to_string(),
+                ));
+            }
+
+            let id = self.base.next(state)?;
+            let exhausted = state
+                .corpus()
+                .get(id)?
+                .borrow()
+                .has_metadata::<ExhaustedMetadata>();
+            if !exhausted {
+                let frontier = self.frontier(state);
+                state
+                    .metadata_or_insert_with(RetirementMetadata::default)
+                    .frontier = frontier;
+                return Ok(id);
+            }
+
+            state
+                .metadata_or_insert_with(RetirementMetadata::default)
+                .retired
+                .insert(id);
+            if self.disable {
+                // queue-like schedulers continue from the current entry, so step back to the one
+                // before this, which is still enabled
+                let prev = state.corpus().prev(id);
+                state.corpus_mut().disable(id)?;
+                self.base.on_remove(state, id, &None)?;
+                *state.corpus_mut().current_mut() = prev;
+            }
+        }
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        self.base.set_current_scheduled(state, next_id)
+    }
+}
+
+/// The number of corpus entries sampled by the [`NoveltyScheduler`] when choosing the next one.
+const CANDIDATES: usize = 8;
+
+/// The energy given by [`NoveltyScore`] to an entry with a [`novelty_score`] of 1.
+const BASE_ENERGY: f64 = 16.0;
+
+/// The most energy given by [`NoveltyScore`] to any entry.
+const MAX_ENERGY: f64 = 512.0;
+
+/// Metadata which tracks how productive a testcase has been, for the [`NoveltyScheduler`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NoveltyMetadata {
+    slots: Vec<usize>,
+    finds: u64,
+    recent: u64,
+}
+
+impl NoveltyMetadata {
+    /// The number of new corpus entries found by mutating this testcase.
+    pub fn finds(&self) -> u64 {
+        self.finds
+    }
+
+    /// The number of new corpus entries found by mutating this testcase recently. This is halved
+    /// every time the testcase is scheduled.
+    pub fn recent(&self) -> u64 {
+        self.recent
+    }
+}
+
+impl_serdeany!(NoveltyMetadata);
+
+/// Metadata which counts how many corpus entries have each car at each position, for the
+/// [`NoveltyScheduler`].
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ConfigurationMetadata {
+    counts: HashMap<usize, u64>,
+}
+
+impl ConfigurationMetadata {
+    fn count(&self, slot: usize) -> u64 {
+        self.counts.get(&slot).copied().unwrap_or_default()
+    }
+}
+
+impl_serdeany!(ConfigurationMetadata);
+
+/// How promising a testcase is to mutate, according to its [`NoveltyMetadata`]: the more new
+/// entries its children found recently, the more legal moves its final board has (if
+/// [`MobilityMetadata`] is available), and the fewer other entries share its car positions, the
+/// higher.
+pub fn novelty_score<S>(state: &S, testcase: &Testcase<PGInput>) -> Result<f64, Error>
+where
+    S: HasMetadata,
+{
+    let novelty = testcase.metadata::<NoveltyMetadata>()?;
+    let counts = state.metadata::<ConfigurationMetadata>()?;
+    // the average over each car of how rare its position is, in (0, 1]
+    let rarity = novelty
+        .slots
+        .iter()
+        .map(|&slot| 1.0 / counts.count(slot).max(1) as f64)
+        .sum::<f64>()
+        / novelty.slots.len().max(1) as f64;
+    let mobility = testcase
+        .metadata::<MobilityMetadata>()
+        .map_or(1.0, |mobility| (1.0 + mobility.moves() as f64).sqrt());
+    Ok((1.0 + novelty.recent as f64) * mobility * rarity)
+}
+
+/// Testcase score which assigns energy (i.e., the number of mutations to make) according to the
+/// [`novelty_score`] of the testcase, for use alongside the [`NoveltyScheduler`].
+///
+/// Only [`libafl::stages::PowerMutationalStage`] asks a [`TestcaseScore`] how many mutations to
+/// make; [`libafl::stages::StdMutationalStage`] always makes a random number of them. To use this
+/// score, the mutation stage must be swapped for
+/// `PowerMutationalStage::<_, NoveltyScore, _, _, _, _, _>::new(mutator)`.
+#[derive(Debug, Clone)]
+pub struct NoveltyScore;
+
+impl<S> TestcaseScore<PGInput, S> for NoveltyScore
+where
+    S: HasMetadata,
+{
+    fn compute(state: &S, entry: &mut Testcase<PGInput>) -> Result<f64, Error> {
+        Ok((BASE_ENERGY * novelty_score(state, entry)?).clamp(1.0, MAX_ENERGY))
+    }
+}
+
+/// Scheduler which prefers corpus entries with a high [`novelty_score`], like a power schedule
+/// driven by puzzle metadata. To keep scheduling cheap on large corpora, a few random entries are
+/// sampled and the best of them is served.
+///
+/// This tracks the [`NoveltyMetadata`] of each testcase, which requires replaying each new entry
+/// from the initial state. The number of mutations made to each entry is only adapted to its score
+/// with the [`NoveltyScore`] and a power mutational stage.
+#[derive(Debug)]
+pub struct NoveltyScheduler<T> {
+    initial: State<T>,
+    cells: usize,
+}
+
+impl<T> NoveltyScheduler<T>
+where
+    T: BoardValue,
+{
+    /// Create a new [`NoveltyScheduler`] for the provided initial state.
+    pub fn new(initial: &State<T>) -> Self {
+        let dim = initial.dimensions();
+        Self {
+            initial: initial.clone(),
+            cells: (*dim.rows()).into() * (*dim.columns()).into(),
+        }
+    }
+
+    fn slots(&self, input: &PGInput) -> Result<Vec<usize>, Error> {
+        let mut replay = self.initial.clone();
+        let mut board = replay
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+        for &(car, direction) in input.moves() {
+            board
+                .shift_car(car, direction)
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+        }
+        drop(board);
+
+        Ok(replay
+            .cars()
+            .iter()
+            .enumerate()
+            .map(|(i, (position, _))| {
+                i * self.cells + position.as_index(replay.dimensions()).unwrap()
+            })
+            .collect())
+    }
+}
+
+impl<S, T> RemovableScheduler<PGInput, S> for NoveltyScheduler<T>
+where
+    S: HasMetadata,
+{
+    fn on_remove(
+        &mut self,
+        state: &mut S,
+        _id: CorpusId,
+        testcase: &Option<Testcase<PGInput>>,
+    ) -> Result<(), Error> {
+        if let Some(novelty) = testcase
+            .as_ref()
+            .and_then(|testcase| testcase.metadata::<NoveltyMetadata>().ok())
+        {
+            let counts = &mut state.metadata_mut::<ConfigurationMetadata>()?.counts;
+            for slot in &novelty.slots {
+                if let Some(count) = counts.get_mut(slot) {
+                    *count = count.saturating_sub(1);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S, T> Scheduler<PGInput, S> for NoveltyScheduler<T>
+where
+    S: HasCorpus<PGInput> + HasMetadata + HasRand,
+    T: BoardValue,
+{
+    fn on_add(&mut self, state: &mut S, id: CorpusId) -> Result<(), Error> {
+        let current_id = *state.corpus().current();
+        let mut testcase = state.corpus().get(id)?.borrow_mut();
+        testcase.set_parent_id_optional(current_id);
+        let slots = self.slots(testcase.load_input(state.corpus())?)?;
+        drop(testcase);
+
+        let counts = &mut state
+            .metadata_or_insert_with(ConfigurationMetadata::default)
+            .counts;
+        for &slot in &slots {
+            *counts.entry(slot).or_default() += 1;
+        }
+        state
+            .corpus()
+            .get(id)?
+            .borrow_mut()
+            .add_metadata(NoveltyMetadata {
+                slots,
+                finds: 0,
+                recent: 0,
+            });
+
+        if let Some(parent) = current_id {
+            let mut parent = state.corpus().get(parent)?.borrow_mut();
+            if let Ok(novelty) = parent.metadata_mut::<NoveltyMetadata>() {
+                novelty.finds += 1;
+                novelty.recent += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self, state: &mut S) -> Result<CorpusId, Error> {
+        if state.corpus().count() == 0 {
+            return Err(Error::empty("No entries in corpus.".to_string()));
+        }
+
+        let mut best = None;
+        for _ in 0..CANDIDATES {
+            let id = random_corpus_id!(state.corpus(), state.rand_mut());
+            let score = novelty_score(state, &state.corpus().get(id)?.borrow())?;
+            if best.is_none_or(|(_, best)| score > best) {
+                best = Some((id, score));
+            }
+        }
+        let (id, _) = best.unwrap();
+
+        state
+            .corpus()
+            .get(id)?
+            .borrow_mut()
+            .metadata_mut::<NoveltyMetadata>()?
+            .recent /= 2;
+        <Self as Scheduler<PGInput, S>>::set_current_scheduled(self, state, Some(id))?;
+        Ok(id)
+    }
+
+    fn set_current_scheduled(
+        &mut self,
+        state: &mut S,
+        next_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        *state.corpus_mut().current_mut() = next_id;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::PGExecutor;
+    use crate::feedbacks::{BlockerMetadata, ViewFeedback};
+    use crate::input::PGInput;
+    use crate::observers::{FinalStateObserver, ViewObserver};
+    use crate::schedulers::{
+        BestFirstScheduler, DepthMeasure, DepthScheduler, ExpansionMetadata, NoveltyMetadata,
+        NoveltyScheduler, NoveltyScore, PriorityScheduler, RetirementMetadata, RetiringScheduler,
+        novelty_score,
+    };
+    use crate::stages::ExhaustedMetadata;
+    use libafl::corpus::{Corpus, CorpusId, InMemoryCorpus, Testcase};
+    use libafl::events::SimpleEventManager;
+    use libafl::feedbacks::NewHashFeedback;
+    use libafl::schedulers::{
+        HasQueueCycles, QueueScheduler, RemovableScheduler, Scheduler, TestcaseScore,
+    };
+    use libafl::state::{HasCorpus, StdState};
+    use libafl::{Evaluator, HasMetadata, HasScheduler, StdFuzzer, feedback_or};
+    use libafl_bolts::rands::StdRand;
+    use libafl_bolts::tuples::tuple_list;
+    use parking_game::Direction;
+    use std::cmp::Reverse;
+    use std::error::Error;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn shallowest_first() -> Result<(), Box<dyn Error>> {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut scheduler = DepthScheduler::new(DepthMeasure::Slides);
+
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let mut ids = Vec::new();
+        for moves in [
+            // three moves, but one slide
+            vec![(one, Direction::Right); 3],
+            vec![],
+            vec![(one, Direction::Right), (two, Direction::Down)],
+        ] {
+            let id = state.corpus_mut().add(Testcase::new(PGInput::new(moves)))?;
+            scheduler.on_add(&mut state, id)?;
+            ids.push(id);
+        }
+
+        assert_eq!(3, scheduler.unexpanded());
+        assert_eq!(ids[1], scheduler.next(&mut state)?);
+        assert_eq!(ids[0], scheduler.next(&mut state)?);
+        assert_eq!(1, scheduler.unexpanded());
+
+        // a new shallow entry is served before the deeper ones that are left
+        let id = state
+            .corpus_mut()
+            .add(Testcase::new(PGInput::new(vec![(two, Direction::Up)])))?;
+        scheduler.on_add(&mut state, id)?;
+        assert_eq!(id, scheduler.next(&mut state)?);
+        assert_eq!(ids[2], scheduler.next(&mut state)?);
+
+        // and then we start again
+        assert_eq!(0, scheduler.queue_cycles());
+        assert_eq!(ids[1], scheduler.next(&mut state)?);
+        assert_eq!(1, scheduler.queue_cycles());
+
+        // removed entries are no longer counted, even before they are dropped from the queue
+        assert_eq!(3, scheduler.unexpanded());
+        scheduler.on_remove(&mut state, ids[0], &None)?;
+        assert_eq!(2, scheduler.unexpanded());
+        assert_eq!(id, scheduler.next(&mut state)?);
+        assert_eq!(ids[2], scheduler.next(&mut state)?);
+        assert_eq!(0, scheduler.unexpanded());
+
+        Ok(())
+    }
+
+    #[test]
+    fn best_first() -> Result<(), Box<dyn Error>> {
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut astar = BestFirstScheduler::new(DepthMeasure::Moves, 1.0);
+        let mut greedy = BestFirstScheduler::new(DepthMeasure::Moves, 3.0);
+
+        let one = NonZeroUsize::new(1).unwrap();
+        let mut ids = Vec::new();
+        for (moves, blockers, depth) in [(0, 2, 2), (3, 1, 1)] {
+            let mut testcase = Testcase::new(PGInput::new(vec![(one, Direction::Right); moves]));
+            testcase.add_metadata(BlockerMetadata::new(blockers, depth));
+            let id = state.corpus_mut().add(testcase)?;
+            astar.on_add(&mut state, id)?;
+            greedy.on_add(&mut state, id)?;
+            ids.push(id);
+        }
+
+        // 0 + 1 * 4 < 3 + 1 * 2
+        assert_eq!(ids[0], astar.next(&mut state)?);
+        // 0 + 3 * 4 > 3 + 3 * 2
+        assert_eq!(ids[1], greedy.next(&mut state)?);
+
+        // any function of the entry may be used as the priority, e.g. deepest first
+        let mut deepest = PriorityScheduler::with_priority(|input: &PGInput, _: &Testcase<_>| {
+            Ok(Reverse(input.moves().len()))
+        });
+        for &id in &ids {
+            deepest.on_add(&mut state, id)?;
+        }
+        assert_eq!(ids[1], deepest.next(&mut state)?);
+
+        // a replaced entry keeps its parent and its place in the cycle, with its new priority
+        state
+            .corpus()
+            .get(ids[0])?
+            .borrow_mut()
+            .set_parent_id(ids[1]);
+        let replacement = Testcase::new(PGInput::new(vec![(one, Direction::Right); 5]));
+        let prev = state.corpus_mut().replace(ids[0], replacement)?;
+        deepest.on_replace(&mut state, ids[0], &prev)?;
+        assert_eq!(1, deepest.unexpanded());
+        assert_eq!(ids[0], deepest.next(&mut state)?);
+        assert_eq!(0, deepest.unexpanded());
+        assert_eq!(ids[0], deepest.next(&mut state)?);
+        assert_eq!(1, deepest.queue_cycles());
+        assert_eq!(
+            Some(ids[1]),
+            state.corpus().get(ids[0])?.borrow().parent_id()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn retire_exhausted() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>("oo...");
+        for disable in [false, true] {
+            let mut state = StdState::new(
+                StdRand::with_seed(0),
+                InMemoryCorpus::<PGInput>::new(),
+                InMemoryCorpus::new(),
+                &mut (),
+                &mut (),
+            )?;
+            let mut scheduler = RetiringScheduler::new(&initial, QueueScheduler::new(), disable);
+
+            let one = NonZeroUsize::new(1).unwrap();
+            let mut ids = Vec::new();
+            for moves in 0..3 {
+                let id = state.corpus_mut().add(Testcase::new(PGInput::new(vec![
+                    (
+                        one,
+                        Direction::Right
+                    );
+                    moves
+                ])))?;
+                scheduler.on_add(&mut state, id)?;
+                ids.push(id);
+            }
+            state
+                .corpus()
+                .get(ids[1])?
+                .borrow_mut()
+                .add_metadata(ExhaustedMetadata::new(2));
+
+            assert_eq!(ids[0], scheduler.next(&mut state)?);
+            assert_eq!(ids[2], scheduler.next(&mut state)?);
+            let meta = state.metadata::<RetirementMetadata>()?;
+            assert_eq!((1, 2), (meta.exhausted(), meta.frontier()));
+            assert_eq!(disable, state.corpus().get(ids[1]).is_err());
+
+            for id in [ids[0], ids[2]] {
+                state
+                    .corpus()
+                    .get(id)?
+                    .borrow_mut()
+                    .add_metadata(ExhaustedMetadata::new(2));
+            }
+            assert!(scheduler.next(&mut state).is_err());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn exhaust_successors() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo.
+        .22
+        "#,
+        );
+        let views = ViewObserver::<u8>::default();
+        let final_state = FinalStateObserver::<u8>::default();
+        let mut feedback = feedback_or!(
+            ViewFeedback::new(&views),
+            NewHashFeedback::new(&final_state)
+        );
+        let mut objective = ();
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut feedback,
+            &mut objective,
+        )?;
+        let scheduler = RetiringScheduler::new(&initial, QueueScheduler::new(), false);
+        let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+        let mut executor = PGExecutor::new(initial, tuple_list!(views, final_state));
+        let mut mgr = SimpleEventManager::printing();
+
+        fn next<S, Z: HasScheduler<PGInput, S>>(fuzzer: &mut Z, state: &mut S) -> CorpusId {
+            fuzzer.scheduler_mut().next(state).unwrap()
+        }
+        let (root, _) =
+            fuzzer.add_input(&mut state, &mut executor, &mut mgr, PGInput::new(vec![]))?;
+        assert_eq!(root, next(&mut fuzzer, &mut state));
+
+        // the root has two successors: car 1 right and car 2 left
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        for (moves, evaluated) in [
+            // neither illegal moves nor deeper inputs are successors
+            (vec![(one, Direction::Left)], 0),
+            (vec![(one, Direction::Right), (two, Direction::Left)], 0),
+            (vec![(one, Direction::Right)], 1),
+            // evaluating the same successor again makes no difference
+            (vec![(one, Direction::Right)], 1),
+        ] {
+            fuzzer.evaluate_input(&mut state, &mut executor, &mut mgr, &PGInput::new(moves))?;
+            let testcase = state.corpus().get(root)?.borrow();
+            assert!(!testcase.has_metadata::<ExhaustedMetadata>());
+            let expansion = testcase.metadata::<ExpansionMetadata>();
+            assert_eq!(evaluated, expansion.map_or(0, |meta| meta.evaluated()));
+        }
+        fuzzer.evaluate_input(
+            &mut state,
+            &mut executor,
+            &mut mgr,
+            &PGInput::new(vec![(two, Direction::Left)]),
+        )?;
+        let testcase = state.corpus().get(root)?.borrow();
+        assert_eq!(2, testcase.metadata::<ExhaustedMetadata>()?.successors());
+        assert!(!testcase.has_metadata::<ExpansionMetadata>());
+        drop(testcase);
+
+        // the root is skipped from now on, and recorded as retired in the state
+        for _ in 0..state.corpus().count() {
+            assert_ne!(root, next(&mut fuzzer, &mut state));
+        }
+        let meta = state.metadata::<RetirementMetadata>()?;
+        assert_eq!(1, meta.exhausted());
+        assert_eq!(state.corpus().count() - 1, meta.frontier());
+
+        Ok(())
+    }
+
+    #[test]
+    fn novelty() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>("oo...\n22...");
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut scheduler = NoveltyScheduler::new(&initial);
+
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let root = state
+            .corpus_mut()
+            .add(Testcase::new(PGInput::new(vec![])))?;
+        scheduler.on_add(&mut state, root)?;
+
+        // both children are found by mutating the root
+        *state.corpus_mut().current_mut() = Some(root);
+        let mut children = Vec::new();
+        for car in [one, two] {
+            let id = state
+                .corpus_mut()
+                .add(Testcase::new(PGInput::new(vec![(car, Direction::Right)])))?;
+            scheduler.on_add(&mut state, id)?;
+            children.push(id);
+        }
+
+        fn score<S: HasCorpus<PGInput> + HasMetadata>(state: &S, id: CorpusId) -> f64 {
+            novelty_score(state, &state.corpus().get(id).unwrap().borrow()).unwrap()
+        }
+        fn recent<S: HasCorpus<PGInput>>(state: &S, id: CorpusId) -> u64 {
+            state
+                .corpus()
+                .get(id)
+                .unwrap()
+                .borrow()
+                .metadata::<NoveltyMetadata>()
+                .unwrap()
+                .recent()
+        }
+        assert_eq!(2, recent(&state, root));
+        // each child shares one car position with the root and has one to itself
+        assert_eq!(0.75, score(&state, children[0]));
+        // all of the root's car positions are shared, but it was productive
+        assert_eq!(3.0 * 0.5, score(&state, root));
+        assert!(score(&state, root) > score(&state, children[1]));
+
+        let energy = NoveltyScore::compute(&state, &mut state.corpus().get(root)?.borrow_mut())?;
+        assert_eq!(16.0 * 1.5, energy);
+
+        // the root is the most promising, and becomes less so once scheduled
+        assert_eq!(root, scheduler.next(&mut state)?);
+        assert_eq!(1, recent(&state, root));
+
+        Ok(())
+    }
+}
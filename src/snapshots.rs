@@ -0,0 +1,245 @@
+//! A cache of boards reached by previous executions, for [`crate::executor::PGExecutor`].
+//!
+//! Snapshot fuzzing with [`crate::feedbacks::FinalStateFeedback`] only resumes from the final
+//! state of the testcase being mutated, and keeps a copy of the state of every corpus entry for as
+//! long as the fuzzer runs. A [`SnapshotCache`] instead keeps the final states of the most recently
+//! used executions, whether or not they were interesting, within a fixed memory budget. Any
+//! execution may then resume from the longest prefix of its input which is cached.
+
+use crate::input::PGInput;
+use parking_game::{Direction, State};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::mem::{size_of, size_of_val};
+use std::num::NonZeroUsize;
+
+struct Snapshot<T> {
+    moves: Vec<(NonZeroUsize, Direction)>,
+    state: State<T>,
+    used: u64,
+    cost: usize,
+}
+
+/// A cache of the states reached after prefixes of inputs, keyed by the hash of the prefix and
+/// evicting the least recently used states when over its memory budget.
+///
+/// Prefixes are looked up by their hash, but each state is kept along with the moves reaching it,
+/// which are compared on every hit, so two prefixes with colliding hashes never share a state.
+pub struct SnapshotCache<T> {
+    budget: usize,
+    used: usize,
+    tick: u64,
+    snapshots: HashMap<u64, Snapshot<T>>,
+    recency: BTreeMap<u64, u64>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<T> SnapshotCache<T>
+where
+    T: Clone,
+{
+    /// Create an empty cache which uses at most roughly `budget` bytes.
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            tick: 0,
+            snapshots: HashMap::new(),
+            recency: BTreeMap::new(),
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    /// The number of states in the cache.
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Whether the cache holds no states.
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// The approximate number of bytes used by the states in the cache.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// The number of lookups which found a cached prefix.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// The number of lookups which found no cached prefix.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// The number of states evicted to stay within the budget.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Finds the longest prefix of the input with the provided prefix hashes which has a cached
+    /// state, returning the length of the prefix and a copy of the state after it.
+    pub fn nearest(&mut self, prefixes: &PrefixHashes) -> Option<(usize, State<T>)> {
+        for (moves, hash) in prefixes.hashes.iter().enumerate().rev() {
+            if let Some(snapshot) = self.snapshots.get_mut(hash)
+                && snapshot.moves == prefixes.moves[..=moves]
+            {
+                self.tick += 1;
+                self.recency.remove(&snapshot.used);
+                self.recency.insert(self.tick, *hash);
+                snapshot.used = self.tick;
+                self.hits += 1;
+                return Some((snapshot.moves.len(), snapshot.state.clone()));
+            }
+        }
+        self.misses += 1;
+        None
+    }
+
+    /// Caches the state reached after all moves of the input with the provided prefix hashes,
+    /// evicting the least recently used states as necessary. The state after no moves is never
+    /// cached, as it is the initial state anyway.
+    pub fn insert(&mut self, prefixes: &PrefixHashes, state: &State<T>) {
+        let Some(&hash) = prefixes.hashes.last() else {
+            return;
+        };
+        let cost = size_of::<Snapshot<T>>()
+            + size_of::<u64>() * 3
+            + size_of_val(state.cars())
+            + size_of_val(prefixes.moves.as_slice());
+        if cost > self.budget {
+            return;
+        }
+
+        self.tick += 1;
+        let snapshot = Snapshot {
+            moves: prefixes.moves.clone(),
+            state: state.clone(),
+            used: self.tick,
+            cost,
+        };
+        if let Some(old) = self.snapshots.insert(hash, snapshot) {
+            self.recency.remove(&old.used);
+            self.used -= old.cost;
+        }
+        self.recency.insert(self.tick, hash);
+        self.used += cost;
+
+        while self.used > self.budget {
+            let (_, oldest) = self.recency.pop_first().unwrap();
+            let evicted = self.snapshots.remove(&oldest).unwrap();
+            self.used -= evicted.cost;
+            self.evictions += 1;
+        }
+    }
+}
+
+/// The hash of each non-empty prefix of the moves of an input, shortest first, by which the
+/// [`SnapshotCache`] identifies the states after them. Compute these once per execution and use
+/// them for both [`SnapshotCache::nearest`] and [`SnapshotCache::insert`].
+#[derive(Debug, Clone)]
+pub struct PrefixHashes {
+    hashes: Vec<u64>,
+    moves: Vec<(NonZeroUsize, Direction)>,
+}
+
+impl PrefixHashes {
+    /// Hash each non-empty prefix of the moves of the provided input.
+    pub fn new(input: &PGInput) -> Self {
+        let mut hash = 0;
+        Self {
+            hashes: input
+                .moves()
+                .iter()
+                .map(|step| {
+                    let mut hasher = DefaultHasher::new();
+                    (hash, step).hash(&mut hasher);
+                    hash = hasher.finish();
+                    hash
+                })
+                .collect(),
+            moves: input.moves().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::input::PGInput;
+    use crate::snapshots::{PrefixHashes, SnapshotCache};
+    use parking_game::{Direction, State};
+    use std::error::Error;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn nearest_and_eviction() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>("oo...\n22...");
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let walk = [
+            (one, Direction::Right),
+            (two, Direction::Right),
+            (one, Direction::Right),
+            (two, Direction::Right),
+        ];
+        let states = (0..=walk.len())
+            .map(|i| {
+                let mut state = initial.clone();
+                let mut board = state.board_mut().unwrap();
+                for &(car, direction) in &walk[..i] {
+                    board.shift_car(car, direction).unwrap();
+                }
+                drop(board);
+                state
+            })
+            .collect::<Vec<State<u8>>>();
+        let prefix = |i: usize| PrefixHashes::new(&PGInput::new(walk[..i].to_vec()));
+
+        let mut cache = SnapshotCache::new(usize::MAX);
+        cache.insert(&prefix(0), &states[0]);
+        assert!(cache.is_empty());
+        assert!(cache.nearest(&prefix(4)).is_none());
+
+        cache.insert(&prefix(1), &states[1]);
+        cache.insert(&prefix(3), &states[3]);
+        let (moves, state) = cache.nearest(&prefix(4)).unwrap();
+        assert_eq!(3, moves);
+        assert_eq!(states[3].cars()[0].0.column(), state.cars()[0].0.column());
+        let (moves, _) = cache.nearest(&prefix(2)).unwrap();
+        assert_eq!(1, moves);
+        // a different input sharing no prefix
+        let other = PGInput::new(vec![(two, Direction::Right)]);
+        assert!(cache.nearest(&PrefixHashes::new(&other)).is_none());
+        assert_eq!((2, 2), (cache.hits(), cache.misses()));
+
+        // a prefix whose hash collides with a cached one does not resume from its state
+        let mut collision = PrefixHashes::new(&other);
+        collision.hashes = prefix(1).hashes;
+        assert!(cache.nearest(&collision).is_none());
+
+        // room for two states: the least recently used is evicted
+        let mut cache = SnapshotCache::new(usize::MAX);
+        cache.insert(&prefix(1), &states[1]);
+        cache.insert(&prefix(3), &states[3]);
+        let budget = cache.used();
+        let mut cache = SnapshotCache::new(budget);
+        cache.insert(&prefix(1), &states[1]);
+        cache.insert(&prefix(2), &states[2]);
+        cache.nearest(&prefix(1));
+        cache.insert(&prefix(3), &states[3]);
+        assert_eq!(2, cache.len());
+        assert_eq!(1, cache.evictions());
+        assert!(cache.used() <= budget);
+        assert_eq!(1, cache.nearest(&prefix(2)).unwrap().0);
+        assert_eq!(3, cache.nearest(&prefix(4)).unwrap().0);
+
+        Ok(())
+    }
+}
@@ -1,16 +1,47 @@
 //! Custom stages for optimized solving of [`parking_game`] puzzles.
 
+use crate::feedbacks::{FinalStateMetadata, SolvedFeedback};
 use crate::input::PGInput;
-use crate::observers::PGObserverTuple;
-use libafl::executors::HasObservers;
+use crate::observers::{PGObserverTuple, ViewObserver, encode_state};
+use libafl::HasMetadata;
+use libafl::corpus::{Corpus, CorpusId, Testcase};
+use libafl::executors::{ExitKind, HasObservers};
 use libafl::feedbacks::Feedback;
-use libafl::observers::ObserversTuple;
+use libafl::observers::{Observer, ObserversTuple};
 use libafl::stages::{Restartable, Stage};
-use libafl::state::{HasCurrentTestcase, HasExecutions};
-use libafl::{ExecutionProcessor, HasFeedback, HasObjective, HasScheduler};
-use libafl_bolts::Error;
-use parking_game::{BoardValue, State};
+use libafl::state::{HasCurrentTestcase, HasExecutions, HasRand, HasSolutions};
+use libafl::{Evaluator, ExecutionProcessor, HasFeedback, HasObjective, HasScheduler};
+use libafl_bolts::rands::Rand;
+use libafl_bolts::{Error, impl_serdeany};
+use parking_game::{Board, BoardValue, Direction, Orientation, State};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::iter;
 use std::marker::PhantomData;
+use std::num::NonZeroUsize;
+
+/// Metadata which marks a testcase as exhausted: every one of its one-move successors has been
+/// executed, so mutating it again is pointless. See [`crate::schedulers::RetiringScheduler`].
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ExhaustedMetadata {
+    successors: usize,
+}
+
+impl ExhaustedMetadata {
+    /// Create new [`ExhaustedMetadata`] for a testcase with the provided number of successors.
+    pub fn new(successors: usize) -> Self {
+        Self { successors }
+    }
+
+    /// The number of one-move successors of the testcase.
+    pub fn successors(&self) -> usize {
+        self.successors
+    }
+}
+
+impl_serdeany!(ExhaustedMetadata);
 
 /// A stage implementation which exhausts the mutation space rather than randomly selecting
 /// mutations.
@@ -83,6 +114,903 @@ where
         //        - truncate the input to its original size
         //        - undo the car shift by applying a shift to the same car in the negative direction
 
+        // TODO(pt.4) mark the testcase as exhausted by adding an `ExhaustedMetadata` to it
+        //  - only do this if every successor was executed (i.e., we didn't return early)
+
+        Ok(())
+    }
+}
+
+/// A stage which searches backward from synthetic goal boards while the fuzzer searches forward
+/// from the initial board, and stitches the two searches together when they meet.
+///
+/// Goal boards are sampled by placing the objective car against the wall ahead of it, then every
+/// other car at a random position in its lane, preserving the order of the cars which share a lane
+/// (as they can never pass each other). Not every goal board is reachable from the initial board,
+/// but those which are not simply never meet the forward search. Moves are reversible, so the
+/// backward search is a breadth-first search over single moves from the goal boards; for each board
+/// found, the move which leads one step closer to a goal is kept in a table keyed by the
+/// [`encode_state`] of the board.
+///
+/// Each time the stage is performed, the backward search is advanced by a fixed budget of boards
+/// and every prefix of the current testcase is looked up in the table. On a hit, the moves of the
+/// prefix and the moves back to the goal are evaluated as a new input, which the objective should
+/// find to be a solution.
+pub struct BidirectionalStage<T> {
+    initial: State<T>,
+    goals: usize,
+    budget: usize,
+    capacity: usize,
+    backward: HashMap<Vec<u8>, Option<(NonZeroUsize, Direction)>>,
+    frontier: VecDeque<State<T>>,
+}
+
+impl<T> BidirectionalStage<T>
+where
+    T: BoardValue,
+{
+    /// Create a new bidirectional stage for the provided initial state, which samples up to `goals`
+    /// goal boards, expands up to `budget` boards of the backward search each time it is performed,
+    /// and never keeps more than `capacity` boards in its table.
+    pub fn new(initial: &State<T>, goals: usize, budget: usize, capacity: usize) -> Self {
+        Self {
+            initial: initial.clone(),
+            goals,
+            budget,
+            capacity,
+            backward: HashMap::new(),
+            frontier: VecDeque::new(),
+        }
+    }
+
+    /// The number of boards found by the backward search so far, including the goal boards.
+    pub fn backward(&self) -> usize {
+        self.backward.len()
+    }
+
+    /// Samples goal boards and adds them to the backward search, returning the number of new goal
+    /// boards.
+    pub fn seed<R: Rand>(&mut self, rand: &mut R) -> usize {
+        let mut added = 0;
+        for _ in 0..self.goals {
+            if self.backward.len() >= self.capacity {
+                break;
+            }
+            if let Some(goal) = self.sample_goal(rand)
+                && let Entry::Vacant(e) = self.backward.entry(encode_state(&goal))
+            {
+                e.insert(None);
+                self.frontier.push_back(goal);
+                added += 1;
+            }
+        }
+        added
+    }
+
+    fn sample_goal<R: Rand>(&self, rand: &mut R) -> Option<State<T>> {
+        let dim = self.initial.dimensions();
+        let (rows, columns): (usize, usize) = ((*dim.rows()).into(), (*dim.columns()).into());
+        let cars = self.initial.cars();
+
+        // (horizontal, lane, offset along the lane) of each car on the initial board
+        let lanes = cars
+            .iter()
+            .map(|(position, car)| match car.orientation() {
+                Orientation::UpDown => {
+                    (false, (*position.column()).into(), (*position.row()).into())
+                }
+                Orientation::LeftRight => {
+                    (true, (*position.row()).into(), (*position.column()).into())
+                }
+            })
+            .collect::<Vec<(bool, usize, usize)>>();
+
+        let mut order = (1..cars.len()).collect::<Vec<_>>();
+        for i in (1..order.len()).rev() {
+            order.swap(i, rand.below(NonZeroUsize::new(i + 1).unwrap()));
+        }
+
+        let mut occupied = vec![false; rows * columns];
+        let mut placed = vec![None; cars.len()];
+        for i in iter::once(0).chain(order) {
+            let (horizontal, lane, initial) = lanes[i];
+            let length: usize = (*cars[i].1.length()).into();
+            let (extent, cell) = if horizontal {
+                (columns, lane * columns)
+            } else {
+                (rows, lane)
+            };
+            let stride = if horizontal { 1 } else { columns };
+
+            let candidates = (0..=extent - length)
+                .filter(|&offset| {
+                    (offset..offset + length).all(|o| !occupied[cell + o * stride])
+                        && lanes
+                            .iter()
+                            .zip(&placed)
+                            .all(|(&other, placed)| match (other, placed) {
+                                ((h, l, before), Some(p)) if h == horizontal && l == lane => {
+                                    (before < initial) == (*p < offset)
+                                }
+                                _ => true,
+                            })
+                })
+                .collect::<Vec<_>>();
+            // the objective car is as far forward as it goes; every other car goes anywhere
+            let offset = if i == 0 {
+                *candidates.last()?
+            } else {
+                rand.choose(candidates)?
+            };
+
+            for o in offset..offset + length {
+                occupied[cell + o * stride] = true;
+            }
+            placed[i] = Some(offset);
+        }
+
+        let mut goal = State::empty((*dim.rows(), *dim.columns())).ok()?;
+        let mut board = goal.board_mut().ok()?;
+        for ((&(horizontal, lane, _), offset), &(_, car)) in lanes.iter().zip(placed).zip(cars) {
+            let (lane, offset) = (T::try_from(lane).ok()?, T::try_from(offset?).ok()?);
+            let position = if horizontal {
+                (lane, offset)
+            } else {
+                (offset, lane)
+            };
+            board.add_car(position, car).ok()?;
+        }
+        drop(board);
+        Some(goal)
+    }
+
+    /// Advances the backward search by up to the budget of boards, returning the number of new
+    /// boards found.
+    pub fn expand(&mut self) -> Result<usize, Error> {
+        let mut added = 0;
+        for _ in 0..self.budget {
+            let Some(current) = self.frontier.pop_front() else {
+                break;
+            };
+            for (i, (_, car)) in current.cars().iter().enumerate() {
+                let car = (NonZeroUsize::new(i + 1).unwrap(), car.orientation());
+                let directions = match car.1 {
+                    Orientation::UpDown => [Direction::Up, Direction::Down],
+                    Orientation::LeftRight => [Direction::Left, Direction::Right],
+                };
+                for direction in directions {
+                    if self.backward.len() >= self.capacity {
+                        return Ok(added);
+                    }
+
+                    let mut next = current.clone();
+                    let mut board = next
+                        .board_mut()
+                        .map_err(|e| Error::illegal_state(e.to_string()))?;
+                    if board.shift_car(car.0, direction).is_err() {
+                        continue;
+                    }
+                    drop(board);
+
+                    // the way back towards the goal is the opposite move
+                    if let Entry::Vacant(e) = self.backward.entry(encode_state(&next)) {
+                        e.insert(Some((car.0, -direction)));
+                        self.frontier.push_back(next);
+                        added += 1;
+                    }
+                }
+            }
+        }
+        Ok(added)
+    }
+
+    /// If the board after some prefix of the provided input was found by the backward search,
+    /// returns that prefix followed by the moves to a goal board.
+    pub fn stitch(&self, input: &PGInput) -> Option<PGInput> {
+        let mut replay = self.initial.clone();
+        let mut board = replay.board_mut().ok()?;
+
+        let mut prefix = 0;
+        while !self.backward.contains_key(&encode_state(board.state())) {
+            let &(car, direction) = input.moves().get(prefix)?;
+            board.shift_car(car, direction).ok()?;
+            prefix += 1;
+        }
+
+        // each step leads to a board found earlier in the backward search, so this terminates
+        let mut moves = input.moves()[..prefix].to_vec();
+        while let Some(&Some((car, direction))) = self.backward.get(&encode_state(board.state())) {
+            board.shift_car(car, direction).ok()?;
+            moves.push((car, direction));
+        }
+        Some(PGInput::new(moves))
+    }
+}
+
+impl<S, T> Restartable<S> for BidirectionalStage<T> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, S, T, Z> Stage<E, EM, S, Z> for BidirectionalStage<T>
+where
+    S: HasCurrentTestcase<PGInput> + HasRand,
+    T: BoardValue,
+    Z: Evaluator<E, EM, PGInput, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        if self.backward.is_empty() {
+            self.seed(state.rand_mut());
+        }
+        self.expand()?;
+
+        if let Some(solution) = self.stitch(&state.current_input_cloned()?) {
+            fuzzer.evaluate_input(state, executor, manager, &solution)?;
+        }
+        Ok(())
+    }
+}
+
+/// A stage which exhaustively searches the boards reachable from the current testcase within a
+/// depth limit, depth-first, without adding them to the corpus. Unlike [`PGMutationStage`], the
+/// memory used is bounded: only the current path and a transposition table of at most `capacity`
+/// boards (to skip boards already found at the same depth or shallower) are kept. Once the table
+/// is full, new boards are no longer recorded and may be searched again.
+///
+/// Each time the stage is performed is one round for the current testcase. If a round finds no
+/// solution, the depth limit of the next round for that testcase is increased, up to a maximum;
+/// the depth limit of each testcase is tracked in its [`DeepeningMetadata`]. The boards near the
+/// testcase are recomputed by every round, and testcases which were searched to the maximum depth
+/// are skipped.
+pub struct IterativeDeepeningStage<T> {
+    initial: State<T>,
+    depth: usize,
+    step: usize,
+    max_depth: usize,
+    capacity: usize,
+    table: HashMap<Vec<u8>, usize>,
+}
+
+/// Metadata which records the depth limit of the next round of the [`IterativeDeepeningStage`]
+/// for a testcase.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeepeningMetadata {
+    depth: usize,
+}
+
+impl DeepeningMetadata {
+    /// The depth limit of the next round for the testcase.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl_serdeany!(DeepeningMetadata);
+
+impl<T> IterativeDeepeningStage<T>
+where
+    T: BoardValue,
+{
+    /// Create a new iterative deepening stage for the provided initial state which searches `depth`
+    /// moves past each testcase in its first round, `step` more in each round after, at most
+    /// `max_depth`, and keeps at most `capacity` boards in its transposition table.
+    pub fn new(
+        initial: &State<T>,
+        depth: usize,
+        step: usize,
+        max_depth: usize,
+        capacity: usize,
+    ) -> Self {
+        Self {
+            initial: initial.clone(),
+            depth,
+            step,
+            max_depth,
+            capacity,
+            table: HashMap::new(),
+        }
+    }
+
+    /// The depth limit of the first round for each testcase.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The largest depth limit of any round.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// The depth limit of the next round for the provided testcase, or [`None`] if it has already
+    /// been searched to the maximum depth.
+    pub fn next_depth(&self, testcase: &Testcase<PGInput>) -> Option<usize> {
+        let depth = testcase
+            .metadata::<DeepeningMetadata>()
+            .map_or(self.depth, DeepeningMetadata::depth);
+        (depth <= self.max_depth).then_some(depth)
+    }
+
+    /// The number of boards in the transposition table after the last round.
+    pub fn transpositions(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Runs one round of the search from the board after the provided input with the provided
+    /// depth limit, passing each board found and the moves reaching it to `visit` until it returns
+    /// `true`. Returns those moves, or [`None`] if `visit` never returned `true`.
+    pub fn search<F>(
+        &mut self,
+        input: &PGInput,
+        depth: usize,
+        mut visit: F,
+    ) -> Result<Option<PGInput>, Error>
+    where
+        F: FnMut(&State<T>, &[(NonZeroUsize, Direction)]) -> Result<bool, Error>,
+    {
+        let mut replay = self.initial.clone();
+        let mut board = replay
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+        for &(car, direction) in input.moves() {
+            if board.shift_car(car, direction).is_err() {
+                // crashing inputs have no board to search from
+                return Ok(None);
+            }
+        }
+
+        self.table.clear();
+        let mut moves = input.moves().to_vec();
+        let found = deepen(
+            &mut self.table,
+            self.capacity,
+            &mut board,
+            &mut moves,
+            depth,
+            &mut visit,
+        )?;
+        Ok(found.then(|| PGInput::new(moves)))
+    }
+}
+
+/// Searches up to `remaining` moves past the provided board, depth-first, leaving the moves to the
+/// board accepted by `visit` (if any) in `moves` and otherwise restoring the board and `moves`.
+fn deepen<T, F>(
+    table: &mut HashMap<Vec<u8>, usize>,
+    capacity: usize,
+    board: &mut Board<&mut State<T>, T>,
+    moves: &mut Vec<(NonZeroUsize, Direction)>,
+    remaining: usize,
+    visit: &mut F,
+) -> Result<bool, Error>
+where
+    T: BoardValue,
+    F: FnMut(&State<T>, &[(NonZeroUsize, Direction)]) -> Result<bool, Error>,
+{
+    if remaining == 0 {
+        return Ok(false);
+    }
+
+    // each car can be moved in two directions; for each board on the current path, this holds the
+    // next of those moves to try from it
+    let choices = 2 * board.state().cars().len();
+    let mut next = vec![0];
+    while let Some(&choice) = next.last() {
+        if choice == choices {
+            // every move from this board has been tried: step back to the board before it
+            next.pop();
+            if !next.is_empty() {
+                let (car, direction) = moves.pop().unwrap();
+                board
+                    .shift_car(car, -direction)
+                    .map_err(|e| Error::illegal_state(e.to_string()))?;
+            }
+            continue;
+        }
+        *next.last_mut().unwrap() += 1;
+
+        let car = NonZeroUsize::new(choice / 2 + 1).unwrap();
+        let directions = match board.state().cars()[choice / 2].1.orientation() {
+            Orientation::UpDown => [Direction::Up, Direction::Down],
+            Orientation::LeftRight => [Direction::Left, Direction::Right],
+        };
+        let direction = directions[choice % 2];
+        // undoing the last move only leads back to where we came from
+        if moves.last() == Some(&(car, -direction)) || board.shift_car(car, direction).is_err() {
+            continue;
+        }
+        moves.push((car, direction));
+
+        let depth = moves.len();
+        let encoding = encode_state(board.state());
+        let fresh = match table.get_mut(&encoding) {
+            Some(seen) if *seen <= depth => false,
+            Some(seen) => {
+                *seen = depth;
+                true
+            }
+            None => {
+                if table.len() < capacity {
+                    table.insert(encoding, depth);
+                }
+                true
+            }
+        };
+        if fresh {
+            if visit(board.state(), moves)? {
+                return Ok(true);
+            }
+            if next.len() < remaining {
+                // search the boards after this one before the next move from the board before it
+                next.push(0);
+                continue;
+            }
+        }
+
+        moves.pop();
+        board
+            .shift_car(car, -direction)
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+    }
+    Ok(false)
+}
+
+impl<S, T> Restartable<S> for IterativeDeepeningStage<T> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, S, T, Z> Stage<E, EM, S, Z> for IterativeDeepeningStage<T>
+where
+    E: HasObservers,
+    E::Observers: PGObserverTuple<T> + ObserversTuple<PGInput, S>,
+    S: HasCurrentTestcase<PGInput> + HasExecutions,
+    T: BoardValue,
+    Z: HasObjective + Evaluator<E, EM, PGInput, S>,
+    Z::Objective: Feedback<EM, PGInput, E::Observers, S>,
+{
+    fn perform(
+        &mut self,
+        fuzzer: &mut Z,
+        executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        let Some(depth) = self.next_depth(&*state.current_testcase()?) else {
+            return Ok(());
+        };
+        let input = state.current_input_cloned()?;
+        let solution = self.search(&input, depth, |node, moves| {
+            // "execute" the board we already have, and only ask the objective about it
+            let input = PGInput::new(moves.to_vec());
+            let board = node
+                .board()
+                .map_err(|e| Error::illegal_state(e.to_string()))?;
+            let mut observers = executor.observers_mut();
+            observers.pre_exec_all(state, &input)?;
+            *state.executions_mut() += 1;
+            observers.initial_board_all(moves.len(), &board);
+            observers.final_board_all(&board);
+            observers.post_exec_all(state, &input, &ExitKind::Ok)?;
+
+            fuzzer.objective_mut().is_interesting(
+                state,
+                manager,
+                &input,
+                &*executor.observers(),
+                &ExitKind::Ok,
+            )
+        })?;
+
+        if let Some(solution) = solution {
+            // evaluate it for real, so that it is recorded as a solution
+            fuzzer.evaluate_input(state, executor, manager, &solution)?;
+        } else {
+            state
+                .current_testcase_mut()?
+                .add_metadata(DeepeningMetadata {
+                    depth: depth + self.step,
+                });
+        }
+        Ok(())
+    }
+}
+
+/// A stage which re-executes every new solution from the initial state, without resuming from
+/// snapshots or any other metadata, and checks that [`SolvedFeedback`] still holds for it. This
+/// catches solutions which are only "solved" because a snapshot was wrong. Any mismatch is an
+/// internal error, reported with a dump of the solution and the board it actually reaches.
+///
+/// This may also be used after fuzzing with [`SolutionVerificationStage::verify_all`].
+pub struct SolutionVerificationStage<T> {
+    initial: State<T>,
+    observers: (ViewObserver<T>, ()),
+    solved: SolvedFeedback<T>,
+    verified: HashSet<CorpusId>,
+}
+
+impl<T> SolutionVerificationStage<T>
+where
+    T: Clone + Default,
+{
+    /// Create a new verification stage for the provided initial state.
+    pub fn new(initial: &State<T>) -> Self {
+        let view = ViewObserver::default();
+        Self {
+            initial: initial.clone(),
+            solved: SolvedFeedback::new(&view),
+            observers: (view, ()),
+            verified: HashSet::new(),
+        }
+    }
+}
+
+impl<T> SolutionVerificationStage<T>
+where
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    /// Verifies every solution which was not verified before, returning how many were verified.
+    pub fn verify_all<EM, S>(&mut self, state: &mut S, manager: &mut EM) -> Result<usize, Error>
+    where
+        S: HasSolutions<PGInput>,
+    {
+        let ids = state
+            .solutions()
+            .ids()
+            .filter(|id| !self.verified.contains(id))
+            .collect::<Vec<_>>();
+        for &id in &ids {
+            let testcase = state.solutions().get(id)?.borrow();
+            let input = testcase
+                .input()
+                .clone()
+                .ok_or_else(|| Error::empty_optional("solution has no input to verify"))?;
+            let recorded = testcase
+                .metadata::<FinalStateMetadata<T>>()
+                .ok()
+                .map(|meta| meta.state().clone());
+            drop(testcase);
+
+            self.verify(state, manager, id, &input, recorded.as_ref())?;
+            self.verified.insert(id);
+        }
+        Ok(ids.len())
+    }
+
+    fn verify<EM, S>(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        id: CorpusId,
+        input: &PGInput,
+        recorded: Option<&State<T>>,
+    ) -> Result<(), Error> {
+        let mut replay = self.initial.clone();
+        let mut board = replay
+            .board_mut()
+            .map_err(|e| Error::illegal_state(e.to_string()))?;
+
+        // exactly what the executor does, but always from the initial board
+        self.observers.0.pre_exec(state, input)?;
+        self.observers.initial_board_all(0, &board);
+        for (i, &(car, direction)) in input.moves().iter().enumerate() {
+            if let Err(e) = board.shift_car(car, direction) {
+                return Err(mismatch(
+                    id,
+                    input,
+                    &format!("move {i} ({car} {direction}) is illegal: {e}"),
+                    board.state(),
+                    recorded,
+                ));
+            }
+            self.observers.moved_all(i, car, direction, &board);
+        }
+        self.observers.final_board_all(&board);
+        self.observers.0.post_exec(state, input, &ExitKind::Ok)?;
+        drop(board);
+
+        if !self
+            .solved
+            .is_interesting(state, manager, input, &self.observers, &ExitKind::Ok)?
+        {
+            return Err(mismatch(
+                id,
+                input,
+                "it does not solve the puzzle",
+                &replay,
+                recorded,
+            ));
+        }
+        if let Some(recorded) = recorded
+            && encode_state(recorded) != encode_state(&replay)
+        {
+            return Err(mismatch(
+                id,
+                input,
+                "its final state metadata differs from the board it reaches",
+                &replay,
+                Some(recorded),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The error for a solution which failed verification, with everything needed to debug it.
+fn mismatch<T: BoardValue>(
+    id: CorpusId,
+    input: &PGInput,
+    reason: &str,
+    reached: &State<T>,
+    recorded: Option<&State<T>>,
+) -> Error {
+    let board = |state: &State<T>| {
+        state
+            .board()
+            .map_or_else(|e| e.to_string(), |board| board.to_string())
+    };
+    let mut dump = format!(
+        "solution {id} failed verification from the initial state: {reason}\n\
+         {} moves: {:?}\n\
+         board reached:\n{}",
+        input.moves().len(),
+        input.moves(),
+        board(reached)
+    );
+    if let Some(recorded) = recorded {
+        dump.push_str(&format!("\nfinal state metadata:\n{}", board(recorded)));
+    }
+    Error::illegal_state(dump)
+}
+
+impl<S, T> Restartable<S> for SolutionVerificationStage<T> {
+    fn should_restart(&mut self, _state: &mut S) -> Result<bool, Error> {
+        Ok(true)
+    }
+
+    fn clear_progress(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<E, EM, S, T, Z> Stage<E, EM, S, Z> for SolutionVerificationStage<T>
+where
+    S: HasSolutions<PGInput>,
+    T: BoardValue + DeserializeOwned + Serialize + 'static,
+{
+    fn perform(
+        &mut self,
+        _fuzzer: &mut Z,
+        _executor: &mut E,
+        state: &mut S,
+        manager: &mut EM,
+    ) -> Result<(), Error> {
+        self.verify_all(state, manager)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::executor::PGExecutor;
+    use crate::feedbacks::SolvedFeedback;
+    use crate::input::PGInput;
+    use crate::observers::ViewObserver;
+    use crate::stages::{BidirectionalStage, IterativeDeepeningStage, SolutionVerificationStage};
+    use libafl::corpus::{Corpus, HasCurrentCorpusId, InMemoryCorpus, Testcase};
+    use libafl::events::SimpleEventManager;
+    use libafl::schedulers::QueueScheduler;
+    use libafl::stages::Stage;
+    use libafl::state::{HasCurrentTestcase, HasExecutions, HasSolutions, StdState};
+    use libafl::{Evaluator, StdFuzzer};
+    use libafl_bolts::rands::StdRand;
+    use libafl_bolts::tuples::tuple_list;
+    use parking_game::{Direction, State};
+    use std::error::Error;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn bidirectional() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo.2
+        ...2
+        ....
+        "#,
+        );
+        let mut stage = BidirectionalStage::new(&initial, 4, 64, 1024);
+
+        // the only goal board: car 2 must be out of the way of the objective car at the wall
+        assert_eq!(1, stage.seed(&mut StdRand::with_seed(0)));
+        assert_eq!(1, stage.backward());
+        assert!(stage.stitch(&PGInput::new(vec![])).is_none());
+
+        while stage.expand()? > 0 {}
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let solution = stage.stitch(&PGInput::new(vec![])).unwrap();
+        assert_eq!(
+            &[
+                (two, Direction::Down),
+                (one, Direction::Right),
+                (one, Direction::Right)
+            ],
+            solution.moves()
+        );
+
+        // the searches meet at the earliest prefix possible, here before the detour
+        let detour = PGInput::new(vec![(one, Direction::Right), (one, Direction::Left)]);
+        assert_eq!(solution.moves(), stage.stitch(&detour).unwrap().moves());
+
+        Ok(())
+    }
+
+    #[test]
+    fn iterative_deepening() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo.2
+        ...2
+        ....
+        "#,
+        );
+        let solved = |node: &State<u8>, _: &[_]| Ok(*node.cars()[0].0.column() == 2);
+
+        // the solution is 3 moves away
+        let mut stage = IterativeDeepeningStage::new(&initial, 2, 1, 3, 4);
+        assert!(stage.search(&PGInput::new(vec![]), 2, solved)?.is_none());
+        assert!(stage.transpositions() <= 4);
+        let solution = stage.search(&PGInput::new(vec![]), 3, solved)?.unwrap();
+        assert_eq!(3, solution.moves().len());
+
+        // without a transposition table, boards are searched again but the solution is the same
+        let mut unbounded = 0;
+        IterativeDeepeningStage::new(&initial, 3, 1, 3, usize::MAX).search(
+            &PGInput::new(vec![]),
+            3,
+            |_, _| {
+                unbounded += 1;
+                Ok(false)
+            },
+        )?;
+        let mut bounded = 0;
+        let mut stage = IterativeDeepeningStage::new(&initial, 3, 1, 3, 0);
+        stage.search(&PGInput::new(vec![]), 3, |_, _| {
+            bounded += 1;
+            Ok(false)
+        })?;
+        assert_eq!(0, stage.transpositions());
+        assert!(bounded > unbounded);
+
+        // the search continues from the board after the input
+        let one = NonZeroUsize::new(1).unwrap();
+        let prefix = PGInput::new(vec![(one, Direction::Right)]);
+        let solution = IterativeDeepeningStage::new(&initial, 3, 1, 3, 16)
+            .search(&prefix, 3, solved)?
+            .unwrap();
+        assert_eq!((one, Direction::Right), solution.moves()[0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deepening_rounds() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo.2
+        ...2
+        ...3
+        ...3
+        ...4
+        ...4
+        ....
+        "#,
+        );
+        let views = ViewObserver::<u8>::default();
+        let mut objective = SolvedFeedback::new(&views);
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut objective,
+        )?;
+        let mut fuzzer = StdFuzzer::new(QueueScheduler::new(), (), objective);
+        let mut executor = PGExecutor::new(initial.clone(), tuple_list!(views));
+        let mut mgr = SimpleEventManager::printing();
+        let mut stage = IterativeDeepeningStage::new(&initial, 1, 1, 2, 16);
+
+        // the solution is 3 moves away from the root (4, 3 and 2 down), out of reach of the maximum
+        // depth
+        let (root, _) =
+            fuzzer.add_input(&mut state, &mut executor, &mut mgr, PGInput::new(vec![]))?;
+        state.set_corpus_id(root)?;
+        for depth in [1, 2] {
+            assert_eq!(Some(depth), stage.next_depth(&*state.current_testcase()?));
+            stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        }
+        assert_eq!(None, stage.next_depth(&*state.current_testcase()?));
+        let executions = *state.executions();
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert_eq!(executions, *state.executions());
+
+        // but only 2 away from another testcase, which starts from the first depth again
+        let four = NonZeroUsize::new(4).unwrap();
+        let (id, _) = fuzzer.add_input(
+            &mut state,
+            &mut executor,
+            &mut mgr,
+            PGInput::new(vec![(four, Direction::Down)]),
+        )?;
+        state.set_corpus_id(id)?;
+        assert_eq!(Some(1), stage.next_depth(&*state.current_testcase()?));
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert_eq!(0, state.solutions().count());
+        stage.perform(&mut fuzzer, &mut executor, &mut state, &mut mgr)?;
+        assert_eq!(1, state.solutions().count());
+
+        Ok(())
+    }
+
+    #[test]
+    fn verify_solutions() -> Result<(), Box<dyn Error>> {
+        let initial = crate::parse_map::<u8>(
+            r#"
+        oo.2
+        ...2
+        ....
+        "#,
+        );
+        let mut state = StdState::new(
+            StdRand::with_seed(0),
+            InMemoryCorpus::<PGInput>::new(),
+            InMemoryCorpus::new(),
+            &mut (),
+            &mut (),
+        )?;
+        let mut stage = SolutionVerificationStage::new(&initial);
+
+        let (one, two) = (NonZeroUsize::new(1).unwrap(), NonZeroUsize::new(2).unwrap());
+        let solution = vec![
+            (two, Direction::Down),
+            (one, Direction::Right),
+            (one, Direction::Right),
+        ];
+        state
+            .solutions_mut()
+            .add(Testcase::new(PGInput::new(solution.clone())))?;
+        assert_eq!(1, stage.verify_all(&mut state, &mut ())?);
+        // solutions are only verified once
+        assert_eq!(0, stage.verify_all(&mut state, &mut ())?);
+
+        // e.g., a snapshot which already had car 2 out of the way
+        let id = state
+            .solutions_mut()
+            .add(Testcase::new(PGInput::new(solution[1..].to_vec())))?;
+        let error = stage.verify_all(&mut state, &mut ()).unwrap_err();
+        assert!(error.to_string().contains("illegal"));
+        state.solutions_mut().remove(id)?;
+
+        state
+            .solutions_mut()
+            .add(Testcase::new(PGInput::new(vec![(one, Direction::Right)])))?;
+        let error = stage.verify_all(&mut state, &mut ()).unwrap_err();
+        assert!(error.to_string().contains("does not solve"));
+
         Ok(())
     }
 }